@@ -0,0 +1,202 @@
+use crate::{
+    midi::{KeyState, PlayedNote},
+    note::Note,
+    transpose,
+};
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+//Largest step of a moving bass line, in semitones
+const BASS_STEP: u8 = 2;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ArpeggioMode {
+    Off,
+    //One chord per time window
+    Window,
+    //One chord until the bass moves
+    Bass,
+}
+
+//Notes of a broken chord, gathered until the window is over or the bass changes
+pub struct Arpeggio {
+    notes: Vec<(u8, KeyState)>,
+    started: u64,
+    last: u64,
+}
+
+impl Arpeggio {
+    pub fn new(stamp: u64) -> Arpeggio {
+        Arpeggio {
+            notes: vec![],
+            started: stamp,
+            last: stamp,
+        }
+    }
+
+    pub fn started(&self) -> u64 {
+        self.started
+    }
+
+    fn bass(&self) -> Option<u8> {
+        self.notes.iter().map(|(key_number, _)| *key_number).min()
+    }
+
+    //A lower note, or a step away from the bass, starts the next chord ("C3 G3 E3 G3 | B2 ...")
+    fn is_bass_change(&self, key_number: u8) -> bool {
+        match self.bass() {
+            Some(bass) if key_number < bass => true,
+            Some(bass) => key_number != bass && key_number - bass <= BASS_STEP,
+            None => false,
+        }
+    }
+
+    //Stamp after which the chord is reported when no other note comes (window in microseconds)
+    pub fn deadline(&self, mode: ArpeggioMode, window: u64) -> u64 {
+        match mode {
+            ArpeggioMode::Off => self.last,
+            ArpeggioMode::Window => self.started + window,
+            ArpeggioMode::Bass => self.last + window,
+        }
+    }
+
+    //Whether a note pressed at this stamp belongs to the next chord
+    pub fn closes(&self, mode: ArpeggioMode, window: u64, stamp: u64, key_number: u8) -> bool {
+        match mode {
+            ArpeggioMode::Off => true,
+            ArpeggioMode::Window => stamp >= self.deadline(mode, window),
+            ArpeggioMode::Bass => {
+                self.is_bass_change(key_number) || stamp >= self.deadline(mode, window)
+            }
+        }
+    }
+
+    //A repeated key keeps its first state
+    pub fn add(&mut self, stamp: u64, key_number: u8, state: KeyState) {
+        if !self.notes.iter().any(|(key, _)| *key == key_number) {
+            self.notes.push((key_number, state));
+        }
+        self.last = stamp;
+    }
+
+    //Notes moved by the transposition (in semitones)
+    pub fn to_notes(&self, transposition: i8) -> Vec<PlayedNote> {
+        let mut notes = self.notes.clone();
+        notes.sort_by_key(|(key_number, _)| *key_number);
+
+        notes
+            .iter()
+            .filter_map(|(key_number, state)| {
+                let key_number = transpose::transpose(*key_number, transposition)?;
+                Note::from(key_number).map(|(note, flat_note)| PlayedNote {
+                    note,
+                    flat_note,
+                    state: *state,
+                })
+            })
+            .collect()
+    }
+}
+
+impl FromStr for ArpeggioMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(ArpeggioMode::Off),
+            "window" => Ok(ArpeggioMode::Window),
+            "bass" => Ok(ArpeggioMode::Bass),
+            _ => Err(format!(
+                "Unknown arpeggio mode \"{s}\" (available: off, window, bass)"
+            )),
+        }
+    }
+}
+
+impl Display for ArpeggioMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArpeggioMode::Off => write!(f, "off"),
+            ArpeggioMode::Window => write!(f, "window"),
+            ArpeggioMode::Bass => write!(f, "bass"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Keys of each broken chord, the keys pressed one at a time (stamp in milliseconds)
+    fn groups(mode: ArpeggioMode, pressed: &[(u64, u8)]) -> Vec<Vec<u8>> {
+        let window = 500_000;
+        let mut groups = vec![];
+        let mut arpeggio: Option<Arpeggio> = None;
+        for (millis, key_number) in pressed {
+            let stamp = millis * 1000;
+            if arpeggio
+                .as_ref()
+                .is_some_and(|group| group.closes(mode, window, stamp, *key_number))
+            {
+                groups.extend(arpeggio.take());
+            }
+            let state = KeyState {
+                velocity: 80,
+                pressed_at: stamp,
+                channel: 0,
+                pedal_held: false,
+            };
+            arpeggio
+                .get_or_insert_with(|| Arpeggio::new(stamp))
+                .add(stamp, *key_number, state);
+        }
+        groups.extend(arpeggio);
+        groups
+            .iter()
+            .map(|group| {
+                group
+                    .to_notes(0)
+                    .iter()
+                    .map(|note| u8::from(&note.note))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn broken_chords_are_gathered_per_window() {
+        let pressed = [
+            (0, 48),
+            (100, 52),
+            (200, 55),
+            (300, 52),
+            (600, 47),
+            (700, 50),
+            (800, 55),
+        ];
+        assert_eq!(
+            groups(ArpeggioMode::Window, &pressed),
+            [vec![48, 52, 55], vec![47, 50, 55]]
+        );
+    }
+
+    #[test]
+    fn broken_chords_are_gathered_until_the_bass_moves() {
+        //Alberti bass without pause between the chords
+        let pressed = [
+            (0, 48),
+            (100, 55),
+            (200, 52),
+            (300, 55),
+            (400, 47),
+            (500, 55),
+            (600, 50),
+        ];
+        assert_eq!(
+            groups(ArpeggioMode::Bass, &pressed),
+            [vec![48, 52, 55], vec![47, 50, 55]]
+        );
+    }
+}
@@ -0,0 +1,388 @@
+use crate::{
+    chord::Chord,
+    error::Error,
+    history::{History, Moment},
+    lilypond, musicxml,
+    notation::Notation,
+    note::{Key, Mode},
+    tempo::TimeSignature,
+};
+use std::str::FromStr;
+
+//Bars per line of a chart
+const BARS_PER_LINE: usize = 4;
+
+//Scores count their durations in sixteenth notes
+const WHOLE_NOTE: u64 = 16;
+
+//Note values from the longest: length in sixteenths, fraction of a whole note and dot
+const NOTE_VALUES: [(u64, u64, bool); 8] = [
+    (16, 1, false),
+    (12, 2, true),
+    (8, 2, false),
+    (6, 4, true),
+    (4, 4, false),
+    (3, 8, true),
+    (2, 8, false),
+    (1, 16, false),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChartFormat {
+    ChordPro,
+    Text,
+    Nashville,
+    MusicXml,
+    LilyPond,
+}
+
+//Part of a bar held by a chord, or silent when there is none
+pub struct Piece<'a> {
+    pub moment: Option<&'a Moment>,
+    pub length: u64,
+    //Whether the chord starts here rather than going on from the previous bar
+    pub onset: bool,
+    //Whether the chord goes on in the next bar
+    pub held: bool,
+}
+
+//What a chart is made of, taken from the chord history and the settings
+pub struct Chart<'a> {
+    pub history: &'a History,
+    pub notation: Notation,
+    pub key: Option<Key>,
+    pub time_signature: TimeSignature,
+}
+
+impl Chart<'_> {
+    fn name(&self, chord: &Chord, format: ChartFormat) -> Result<String, Error> {
+        match (format, &self.key) {
+            (ChartFormat::Nashville, Some(key)) => Ok(self.notation.nashville(chord, key)),
+            (ChartFormat::Nashville, None) => Err(Error::Parse(
+                "Nashville charts need a key (set key <key>)".to_string(),
+            )),
+            _ => Ok(self.notation.chord(chord)),
+        }
+    }
+
+    pub fn beats_per_bar(&self) -> u64 {
+        self.time_signature.beats.max(1) as u64
+    }
+
+    //Beat (from 0) at which each chord of the history starts, without tempo every chord gets a bar of its own
+    pub fn beats(&self) -> Vec<u64> {
+        let moments = self.history.moments();
+        let beats_per_bar = self.beats_per_bar();
+        let timed_by_position = moments.iter().all(|moment| moment.position.is_some());
+
+        moments
+            .iter()
+            .enumerate()
+            .map(|(i, moment)| match moment.position {
+                Some(position) if timed_by_position => {
+                    (position.bar - 1) * beats_per_bar + position.beat - 1
+                }
+                _ => i as u64 * beats_per_bar,
+            })
+            .collect()
+    }
+
+    //Sixteenths of a beat and of a bar
+    pub fn beat_length(&self) -> u64 {
+        WHOLE_NOTE / self.time_signature.unit.max(1) as u64
+    }
+
+    pub fn bar_length(&self) -> u64 {
+        self.beats_per_bar() * self.beat_length()
+    }
+
+    //Bars of a score, each chord lasts until the next one and the last one until the end of its bar
+    pub fn measures(&self) -> Vec<Vec<Piece<'_>>> {
+        let beats = self.beats();
+        let beat_length = self.beat_length();
+        let bar_length = self.bar_length();
+
+        let mut spans = vec![];
+        for (i, moment) in self.history.moments().iter().enumerate() {
+            let start = beats[i] * beat_length;
+            let end = match beats.get(i + 1) {
+                Some(next) => next * beat_length,
+                None => (start / bar_length + 1) * bar_length,
+            };
+            if end > start {
+                spans.push((start, end, moment));
+            }
+        }
+
+        let (Some(first), Some(last)) = (spans.first(), spans.last()) else {
+            return vec![];
+        };
+        let first_bar = first.0 / bar_length;
+        let last_bar = (last.1 - 1) / bar_length;
+
+        let mut measures = vec![];
+        for bar in first_bar..=last_bar {
+            let bar_start = bar * bar_length;
+            let bar_end = bar_start + bar_length;
+
+            let mut pieces = vec![];
+            let mut time = bar_start;
+            for (start, end, moment) in &spans {
+                if *end <= bar_start || *start >= bar_end {
+                    continue;
+                }
+                if *start > time {
+                    pieces.push(Piece {
+                        moment: None,
+                        length: start - time,
+                        onset: true,
+                        held: false,
+                    });
+                }
+                let to = (*end).min(bar_end);
+                pieces.push(Piece {
+                    moment: Some(moment),
+                    length: to - (*start).max(bar_start),
+                    onset: *start >= bar_start,
+                    held: *end > bar_end,
+                });
+                time = to;
+            }
+            if time < bar_end {
+                pieces.push(Piece {
+                    moment: None,
+                    length: bar_end - time,
+                    onset: true,
+                    held: false,
+                });
+            }
+            measures.push(pieces);
+        }
+
+        measures
+    }
+
+    //Chords of each bar, the chord still sounding at the start of a bar comes first
+    fn bars(&self, format: ChartFormat) -> Result<Vec<Vec<String>>, Error> {
+        let beats_per_bar = self.beats_per_bar();
+
+        let mut timed = vec![];
+        for (moment, beat) in self.history.moments().iter().zip(self.beats()) {
+            timed.push((beat, self.name(&moment.chord, format)?));
+        }
+        //A later chord on the same beat replaces the earlier one
+        timed.dedup_by(|later, earlier| {
+            let same_beat = later.0 == earlier.0;
+            if same_beat {
+                earlier.1 = later.1.clone();
+            }
+            same_beat
+        });
+
+        let (Some(first), Some(last)) = (timed.first(), timed.last()) else {
+            return Ok(vec![]);
+        };
+        let first_bar = first.0 / beats_per_bar;
+        let last_bar = last.0 / beats_per_bar;
+
+        let mut bars = vec![];
+        let mut sounding: Option<String> = None;
+        for bar in first_bar..=last_bar {
+            let changes: Vec<&(u64, String)> = timed
+                .iter()
+                .filter(|(beat, _)| beat / beats_per_bar == bar)
+                .collect();
+
+            let mut chords = vec![];
+            match (changes.first(), &sounding) {
+                (Some((beat, _)), Some(sounding)) if beat % beats_per_bar != 0 => {
+                    chords.push(sounding.clone())
+                }
+                (None, Some(sounding)) => chords.push(sounding.clone()),
+                _ => (),
+            }
+            for (_, name) in &changes {
+                chords.push(name.clone());
+            }
+
+            if let Some(name) = chords.last() {
+                sounding = Some(name.clone());
+            }
+            bars.push(chords);
+        }
+
+        Ok(bars)
+    }
+
+    pub fn render(&self, format: ChartFormat) -> Result<String, Error> {
+        match format {
+            ChartFormat::MusicXml => return Ok(musicxml::render(self)),
+            ChartFormat::LilyPond => return Ok(lilypond::render(self)),
+            _ => (),
+        }
+
+        let bars = self.bars(format)?;
+        let bpm = self
+            .history
+            .moments()
+            .iter()
+            .rev()
+            .find_map(|moment| moment.position.map(|position| position.bpm));
+
+        let mut lines = vec![];
+        match format {
+            ChartFormat::ChordPro => {
+                lines.push(format!("{{time: {}}}", self.time_signature));
+                if let Some(bpm) = bpm {
+                    lines.push(format!("{{tempo: {bpm}}}"));
+                }
+                if let Some(key) = &self.key {
+                    lines.push(format!("{{key: {}}}", self.key_name(key)));
+                }
+            }
+            ChartFormat::Nashville => {
+                if let Some(key) = &self.key {
+                    lines.push(format!(
+                        "Key: {} ({})",
+                        self.key_name(key),
+                        self.time_signature
+                    ));
+                }
+            }
+            ChartFormat::Text | ChartFormat::MusicXml | ChartFormat::LilyPond => (),
+        }
+
+        //"| Dm7 | G7 | Cmaj7 | % |"
+        let mut previous: Option<&Vec<String>> = None;
+        for line in bars.chunks(BARS_PER_LINE) {
+            let mut cells = vec![];
+            for chords in line {
+                let cell = match format {
+                    ChartFormat::ChordPro => chords
+                        .iter()
+                        .map(|name| format!("[{name}]"))
+                        .collect::<Vec<String>>()
+                        .join(" "),
+                    _ if chords.len() == 1 && previous == Some(chords) => "%".to_string(),
+                    _ => chords.join(" "),
+                };
+                cells.push(cell);
+                previous = Some(chords);
+            }
+            lines.push(format!("| {} |", cells.join(" | ")));
+        }
+
+        Ok(lines.join("\n"))
+    }
+
+    fn key_name(&self, key: &Key) -> String {
+        match key.mode {
+            Mode::Major => self.notation.tonic(key),
+            Mode::Minor => format!("{}m", self.notation.tonic(key)),
+        }
+    }
+}
+
+//Note values adding up to the length, longest first, to be tied together
+pub fn note_values(length: u64) -> Vec<(u64, u64, bool)> {
+    let mut values = vec![];
+    let mut left = length;
+    while left > 0 {
+        let value = NOTE_VALUES
+            .iter()
+            .find(|(sixteenths, _, _)| *sixteenths <= left)
+            .unwrap_or(&NOTE_VALUES[7]);
+        values.push(*value);
+        left -= value.0.min(left);
+    }
+    values
+}
+
+impl FromStr for ChartFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chordpro" => Ok(ChartFormat::ChordPro),
+            "text" => Ok(ChartFormat::Text),
+            "nashville" => Ok(ChartFormat::Nashville),
+            "musicxml" => Ok(ChartFormat::MusicXml),
+            "lilypond" => Ok(ChartFormat::LilyPond),
+            _ => Err(format!(
+                "Unknown chart format \"{s}\" (available: chordpro, text, nashville, musicxml, lilypond)"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chord, config::Config, note::notes, tempo::Position};
+
+    //Chords of the keys starting at bar and beat, at 120 bpm
+    fn history(chords: &[(&[u8], u64, u64)]) -> History {
+        let mut history = History::default();
+        for (key_numbers, bar, beat) in chords {
+            let notes = notes(key_numbers);
+            let chord = chord::to_chord(&notes, &notes[0], &Config::default().scoring).remove(0);
+            history.push(Moment {
+                stamp: ((bar - 1) * 4 + beat - 1) * 500_000,
+                position: Some(Position {
+                    bar: *bar,
+                    beat: *beat,
+                    bpm: 120,
+                }),
+                notes,
+                chord,
+            });
+        }
+        history
+    }
+
+    //ii V I in C, the tonic held two bars
+    fn two_five_one() -> History {
+        history(&[
+            (&[62, 65, 69, 72], 1, 1),
+            (&[55, 59, 62, 65], 2, 1),
+            (&[60, 64, 67, 71], 3, 1),
+            (&[62, 65, 69, 72], 5, 1),
+        ])
+    }
+
+    fn render(history: &History, key: Option<&str>, format: ChartFormat) -> Result<String, Error> {
+        let mut config = Config::default();
+        if let Some(key) = key {
+            config.set("key", key).unwrap();
+        }
+        Chart {
+            history,
+            notation: config.notation(),
+            key: config.scoring.key,
+            time_signature: config.time_signature,
+        }
+        .render(format)
+    }
+
+    #[test]
+    fn text_chart_repeats_held_bars() {
+        let chart = render(&two_five_one(), None, ChartFormat::Text).unwrap();
+        assert_eq!(chart, "| Dmin7 | G7 | Cmaj7 | % |\n| Dmin7 |");
+    }
+
+    #[test]
+    fn chordpro_chart_has_the_directives() {
+        let chart = render(&two_five_one(), Some("C"), ChartFormat::ChordPro).unwrap();
+        assert_eq!(
+            chart,
+            "{time: 4/4}\n{tempo: 120}\n{key: C}\n| [Dmin7] | [G7] | [Cmaj7] | [Cmaj7] |\n| [Dmin7] |"
+        );
+    }
+
+    #[test]
+    fn nashville_chart_needs_a_key() {
+        let chart = render(&two_five_one(), Some("C"), ChartFormat::Nashville).unwrap();
+        assert_eq!(chart, "Key: C (4/4)\n| 2min7 | 5⁷ | 1maj7 | % |\n| 2min7 |");
+        assert!(render(&two_five_one(), None, ChartFormat::Nashville).is_err());
+    }
+}
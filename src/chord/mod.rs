@@ -1,6 +1,13 @@
-use std::ops::{Deref, DerefMut};
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
 
-use crate::note::Note;
+use crate::{
+    note::{Key, Mode, Note},
+    score::{Component, Score, Scoring, Weights},
+    voicing,
+};
 
 struct IntervalFlag(u16);
 
@@ -45,10 +52,320 @@ macro_rules! has_interval_exclusive {
 
 macro_rules! remove_interval {
     ($state:tt, $flags:expr) => {
-        $state & !(*$flags)
+        $state &= !(*$flags)
     };
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum Quality {
+    Power,
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Suspended,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Second {
+    Minor,
+    Major,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Fourth {
+    Perfect,
+    Augmented,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Suspension {
+    pub second: Option<Second>,
+    pub fourth: Option<Fourth>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Seventh {
+    Diminished,
+    Minor,
+    Major,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Extension {
+    pub seventh: Seventh,
+    pub degree: u8,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Tension {
+    FlatNinth,
+    Ninth,
+    SharpNinth,
+    Fourth,
+    FlatFifth,
+    FlatThirteenth,
+    Thirteenth,
+    MinorSeventh,
+    MajorSeventh,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Inversion {
+    Root,
+    First,
+    Second,
+    Third,
+}
+
+impl Inversion {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Inversion::Root => "root",
+            Inversion::First => "first",
+            Inversion::Second => "second",
+            Inversion::Third => "third",
+        }
+    }
+}
+
+impl fmt::Display for Inversion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Inversion::Root => write!(f, "root position"),
+            _ => write!(f, "{} inversion", self.name()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Stacking {
+    Polychord,
+    UpperStructure,
+}
+
+//Chord played in the top voices over the lower chord
+#[derive(Clone, PartialEq)]
+pub struct Upper {
+    pub chord: Box<Chord>,
+    pub stacking: Stacking,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Chord {
+    pub root: Note,
+    pub bass: Option<Note>,
+    pub quality: Quality,
+    pub suspension: Option<Suspension>,
+    pub extension: Option<Extension>,
+    pub tensions: Vec<Tension>,
+    pub upper: Option<Upper>,
+    pub score: Score,
+}
+
+impl Chord {
+    fn new(root: Note, quality: Quality) -> Chord {
+        Chord {
+            root,
+            bass: None,
+            quality,
+            suspension: None,
+            extension: None,
+            tensions: vec![],
+            upper: None,
+            score: Score::default(),
+        }
+    }
+
+    pub fn weight(&self) -> u32 {
+        self.score.total()
+    }
+
+    //Same name, whatever the score
+    pub fn is_same(&self, other: &Chord) -> bool {
+        self.root == other.root
+            && self.bass == other.bass
+            && self.quality == other.quality
+            && self.suspension == other.suspension
+            && self.extension == other.extension
+            && self.tensions == other.tensions
+            && self.upper == other.upper
+    }
+
+    //Root position triad without any added note
+    pub fn is_triad(&self) -> bool {
+        matches!(
+            self.quality,
+            Quality::Major | Quality::Minor | Quality::Diminished | Quality::Augmented
+        ) && self.suspension.is_none()
+            && self.extension.is_none()
+            && self.tensions.is_empty()
+            && self.bass.is_none()
+    }
+
+    pub fn is_dominant(&self) -> bool {
+        self.quality == Quality::Major
+            && self
+                .extension
+                .is_some_and(|extension| extension.seventh == Seventh::Minor)
+    }
+
+    //Semitones from the root to the note, in 0..12
+    pub fn interval(&self, note: &Note) -> u8 {
+        (12 + note.pitch_class() - self.root.pitch_class()) % 12
+    }
+
+    //Semitones between the root and the bass, 0 in root position
+    fn bass_interval(&self) -> u8 {
+        match &self.bass {
+            Some(bass) => self.interval(bass),
+            None => 0,
+        }
+    }
+
+    //Root, third (or suspension), fifth and seventh as an interval bitmap
+    pub fn chord_tones(&self) -> u16 {
+        let mut tones = *PERFECT_FIRST;
+        tones |= match self.quality {
+            Quality::Power | Quality::Suspended => *PERFECT_FIFTH,
+            Quality::Major => *MAJOR_THIRD | *PERFECT_FIFTH,
+            Quality::Minor => *MINOR_THIRD | *PERFECT_FIFTH,
+            Quality::Diminished => *MINOR_THIRD | *DIMINISHED_FIFTH,
+            Quality::Augmented => *MAJOR_THIRD | *MINOR_SIXTH,
+        };
+        if let Some(suspension) = &self.suspension {
+            tones |= match suspension.second {
+                Some(Second::Major) => *MAJOR_SECOND,
+                Some(Second::Minor) => *MINOR_SECOND,
+                None => 0,
+            };
+            tones |= match suspension.fourth {
+                Some(Fourth::Perfect) => *PERFECT_FOURTH,
+                Some(Fourth::Augmented) => *DIMINISHED_FIFTH,
+                None => 0,
+            };
+        }
+        if let Some(extension) = &self.extension {
+            tones |= match extension.seventh {
+                Seventh::Diminished => *MAJOR_SIXTH,
+                Seventh::Minor => *MINOR_SEVENTH,
+                Seventh::Major => *MAJOR_SEVENTH,
+            };
+        }
+        tones
+    }
+
+    //Every note the chord asks for as an interval bitmap: chord tones, extension, tensions and
+    //bass, polychords by their lower chord
+    pub fn intervals(&self) -> u16 {
+        let mut intervals = self.chord_tones();
+        intervals |= match self.extension.map(|extension| extension.degree) {
+            Some(9) => *MAJOR_SECOND,
+            Some(11) => *MAJOR_SECOND | *PERFECT_FOURTH,
+            Some(13) => *MAJOR_SECOND | *PERFECT_FOURTH | *MAJOR_SIXTH,
+            _ => 0,
+        };
+        for tension in &self.tensions {
+            intervals |= match tension {
+                Tension::FlatNinth => *MINOR_SECOND,
+                Tension::Ninth => *MAJOR_SECOND,
+                Tension::SharpNinth => *MINOR_THIRD,
+                Tension::Fourth => *PERFECT_FOURTH,
+                Tension::FlatFifth => *DIMINISHED_FIFTH,
+                Tension::FlatThirteenth => *MINOR_SIXTH,
+                Tension::Thirteenth => *MAJOR_SIXTH,
+                Tension::MinorSeventh => *MINOR_SEVENTH,
+                Tension::MajorSeventh => *MAJOR_SEVENTH,
+            };
+        }
+        intervals | 0b1 << self.bass_interval()
+    }
+
+    pub fn is_tension(&self, note: &Note) -> bool {
+        self.chord_tones() & (0b1 << self.interval(note)) == 0
+    }
+
+    //Third and seventh, the notes that define the chord quality
+    pub fn is_guide_tone(&self, note: &Note) -> bool {
+        let guide_tones = *MINOR_THIRD | *MAJOR_THIRD | *MINOR_SEVENTH | *MAJOR_SEVENTH;
+        let guide_tones = match &self.extension {
+            Some(extension) if extension.seventh == Seventh::Diminished => {
+                guide_tones | *MAJOR_SIXTH
+            }
+            _ => guide_tones,
+        };
+        self.chord_tones() & guide_tones & (0b1 << self.interval(note)) != 0
+    }
+
+    //0 when the chord is diatonic to the key, 1 for a borrowed quality, 2 for a chromatic root
+    fn key_misfit(&self, key: &Key) -> u32 {
+        let Some(degree) = key.degree(self.root.pitch_class()) else {
+            return 2;
+        };
+
+        let fits = matches!(
+            (key.mode, degree, self.quality),
+            (_, _, Quality::Power | Quality::Suspended)
+                | (Mode::Major, 0 | 3 | 4, Quality::Major)
+                | (Mode::Major, 1 | 2 | 5, Quality::Minor)
+                | (Mode::Major, 6, Quality::Diminished)
+                | (Mode::Minor, 0 | 3 | 4, Quality::Minor)
+                | (Mode::Minor, 2 | 4 | 5 | 6, Quality::Major)
+                | (Mode::Minor, 1 | 6, Quality::Diminished)
+        );
+
+        if fits {
+            0
+        } else {
+            1
+        }
+    }
+
+    //None when the bass is not the third, fifth or seventh of the chord
+    pub fn inversion(&self) -> Option<Inversion> {
+        let has_third = matches!(
+            self.quality,
+            Quality::Major | Quality::Minor | Quality::Diminished | Quality::Augmented
+        );
+        let fifth = match self.quality {
+            Quality::Diminished => 6,
+            Quality::Augmented => 8,
+            _ => 7,
+        };
+        let seventh = self.extension.map(|extension| match extension.seventh {
+            Seventh::Diminished => 9,
+            Seventh::Minor => 10,
+            Seventh::Major => 11,
+        });
+
+        match self.bass_interval() {
+            0 => Some(Inversion::Root),
+            3 | 4 if has_third => Some(Inversion::First),
+            interval if interval == fifth => Some(Inversion::Second),
+            interval if Some(interval) == seventh => Some(Inversion::Third),
+            _ => None,
+        }
+    }
+
+    pub fn figured_bass(&self) -> Option<&'static str> {
+        if matches!(self.quality, Quality::Power | Quality::Suspended) {
+            return None;
+        }
+
+        let figures = match (self.inversion()?, self.extension.is_some()) {
+            (Inversion::Root, false) => "5/3",
+            (Inversion::First, false) => "6",
+            (Inversion::Second, false) => "6/4",
+            (Inversion::Third, false) => return None,
+            (Inversion::Root, true) => "7",
+            (Inversion::First, true) => "6/5",
+            (Inversion::Second, true) => "4/3",
+            (Inversion::Third, true) => "4/2",
+        };
+        Some(figures)
+    }
+}
+
 fn is_sus(interval_bitmap: u16) -> bool {
     is_sus2(interval_bitmap) || is_sus4(interval_bitmap)
 }
@@ -67,10 +384,8 @@ fn is_sus2(interval_bitmap: u16) -> bool {
     has_interval!(interval_bitmap, MAJOR_SECOND) || has_interval!(interval_bitmap, MINOR_SECOND)
 }
 
+//With the fifth only the perfect fourth is suspended ("Csus4"), the ♯4 is then a ♭5 tension
 fn is_sus4(interval_bitmap: u16) -> bool {
-    if has_interval!(interval_bitmap, PERFECT_FIFTH) {
-        return false;
-    }
     if (has_interval!(interval_bitmap, MINOR_SEVENTH)
         || has_interval!(interval_bitmap, MAJOR_SEVENTH))
         && has_interval!(interval_bitmap, PERFECT_FOURTH)
@@ -78,8 +393,13 @@ fn is_sus4(interval_bitmap: u16) -> bool {
         return false;
     }
 
-    has_interval!(interval_bitmap, PERFECT_FOURTH)
-        || has_interval!(interval_bitmap, DIMINISHED_FIFTH)
+    match has_interval!(interval_bitmap, PERFECT_FIFTH) {
+        true => has_interval!(interval_bitmap, PERFECT_FOURTH),
+        false => {
+            has_interval!(interval_bitmap, PERFECT_FOURTH)
+                || has_interval!(interval_bitmap, DIMINISHED_FIFTH)
+        }
+    }
 }
 
 fn is_min(interval_bitmap: u16) -> bool {
@@ -90,49 +410,77 @@ fn is_maj(interval_bitmap: u16) -> bool {
     has_interval!(interval_bitmap, MAJOR_THIRD)
 }
 
+//Minor third and diminished fifth, checked before the minor quality
 fn is_dim(interval_bitmap: u16) -> bool {
-    has_interval!(interval_bitmap, MINOR_THIRD)
-        && has_interval!(interval_bitmap, PERFECT_FOURTH)
+    is_min(interval_bitmap)
+        && has_interval!(interval_bitmap, DIMINISHED_FIFTH)
         && !has_interval!(interval_bitmap, PERFECT_FIFTH)
 }
 
+//Major third and augmented fifth, checked before the major quality
 fn is_aug(interval_bitmap: u16) -> bool {
-    has_interval!(interval_bitmap, MINOR_THIRD)
+    has_interval!(interval_bitmap, MAJOR_THIRD)
         && has_interval!(interval_bitmap, MINOR_SIXTH)
         && !has_interval!(interval_bitmap, PERFECT_FIFTH)
 }
 
-pub fn to_chord(notes: Vec<Note>) -> Vec<(String, u32)> {
+pub fn to_chord(notes: &[Note], bass: &Note, scoring: &Scoring) -> Vec<Chord> {
+    to_chord_split(notes, bass, None, scoring)
+}
+
+//Keys below the split are the left hand, stacked chords are then only divided between the hands
+pub fn to_chord_split(
+    notes: &[Note],
+    bass: &Note,
+    split: Option<&Note>,
+    scoring: &Scoring,
+) -> Vec<Chord> {
+    let mut chords = candidates(notes, bass, scoring);
+
+    chords.extend(voicing::polychords(notes, split, scoring));
+
+    chords.sort_by_key(|chord| chord.weight());
+
+    chords
+}
+
+//Every chord that can be built on one of the notes, notes are expected sorted by register
+pub fn candidates(notes: &[Note], bass: &Note, scoring: &Scoring) -> Vec<Chord> {
     let mut chords = vec![];
+    let weights = &scoring.weights;
 
-    if notes.len() == 0 {
-        return chords;
-    }
+    //Octave doublings give the same chord, only try each pitch class once
+    let mut tried_roots: u16 = 0;
 
-    let first_note = notes.first().unwrap();
+    for potential_root in notes {
+        if tried_roots & (0b1 << potential_root.pitch_class()) != 0 {
+            continue;
+        }
+        tried_roots |= 0b1 << potential_root.pitch_class();
 
-    for potential_root in &notes {
-        let chord = to_chord_root(&notes, potential_root);
-        match chord {
-            Some((name, weight)) => {
-                if first_note != potential_root {
-                    let new_name = format!("{name}/{}{}", first_note.name, first_note.accidental);
-                    chords.push((new_name, weight + 3));
-                } else {
-                    chords.push((name, weight));
-                }
+        if let Some(mut chord) = to_chord_root(notes, potential_root, weights) {
+            if bass.pitch_class() != potential_root.pitch_class() {
+                chord.bass = Some(*bass);
+                chord.score.add(Component::SlashBass, weights.slash_bass);
             }
-            None => (),
+            if let Some(key) = &scoring.key {
+                chord
+                    .score
+                    .add(Component::KeyFit, weights.key_fit * chord.key_misfit(key));
+            }
+            chord.score.add(
+                Component::VoicingRegister,
+                weights.voicing_register * voicing::register_units(&chord, notes),
+            );
+            chords.push(chord);
         }
     }
 
-    chords.sort_by(|a, b| a.1.cmp(&b.1));
-
     chords
 }
 
-pub fn to_chord_root(notes: &Vec<Note>, root: &Note) -> Option<(String, u32)> {
-    if notes.len() == 0 {
+pub fn to_chord_root(notes: &[Note], root: &Note, weights: &Weights) -> Option<Chord> {
+    if notes.is_empty() {
         return None;
     }
 
@@ -148,219 +496,264 @@ pub fn to_chord_root(notes: &Vec<Note>, root: &Note) -> Option<(String, u32)> {
         interval_bitmap |= 0b1 << (semitone % 12);
     }
 
-    interval_bitmap = remove_interval!(interval_bitmap, PERFECT_FIRST);
+    remove_interval!(interval_bitmap, PERFECT_FIRST);
 
     if has_interval_exclusive!(interval_bitmap, PERFECT_FIFTH) {
-        return Some((format!("{}5", root.name), 0));
+        return Some(Chord::new(*root, Quality::Power));
     }
 
-    let mut weight = 0;
+    let mut score = Score::default();
 
     //Quality
-    let mut is_major = false;
-    let mut is_minor = false;
-    let mut quality = String::new();
-    if is_maj(interval_bitmap) {
-        //quality is major but is not displayed
-        is_major = true;
-        interval_bitmap = remove_interval!(interval_bitmap, MAJOR_THIRD);
-        weight += 1;
+    let mut quality = None;
+    if is_dim(interval_bitmap) {
+        quality = Some(Quality::Diminished);
+        remove_interval!(interval_bitmap, MINOR_THIRD);
+        remove_interval!(interval_bitmap, DIMINISHED_FIFTH);
+        score.add(Component::Quality, weights.altered_quality);
+    } else if is_aug(interval_bitmap) {
+        quality = Some(Quality::Augmented);
+        remove_interval!(interval_bitmap, MAJOR_THIRD);
+        remove_interval!(interval_bitmap, MINOR_SIXTH);
+        score.add(Component::Quality, weights.altered_quality);
+    } else if is_maj(interval_bitmap) {
+        quality = Some(Quality::Major);
+        remove_interval!(interval_bitmap, MAJOR_THIRD);
+        score.add(Component::Quality, weights.quality);
         if !has_interval!(interval_bitmap, PERFECT_FIFTH) {
-            weight += 3;
+            score.add(Component::MissingFifth, weights.missing_fifth);
         }
     } else if is_min(interval_bitmap) {
-        is_minor = true;
-        quality = "min".to_string();
-        interval_bitmap = remove_interval!(interval_bitmap, MINOR_THIRD);
-        weight += 1;
+        quality = Some(Quality::Minor);
+        remove_interval!(interval_bitmap, MINOR_THIRD);
+        score.add(Component::Quality, weights.quality);
         if !has_interval!(interval_bitmap, PERFECT_FIFTH) {
-            weight += 3;
+            score.add(Component::MissingFifth, weights.missing_fifth);
         }
-    } else if is_dim(interval_bitmap) {
-        quality = "dim".to_string();
-        interval_bitmap = remove_interval!(interval_bitmap, MINOR_THIRD);
-        interval_bitmap = remove_interval!(interval_bitmap, PERFECT_FOURTH);
-        weight += 3;
-    } else if is_aug(interval_bitmap) {
-        quality = "aug".to_string();
-        interval_bitmap = remove_interval!(interval_bitmap, MAJOR_THIRD);
-        interval_bitmap = remove_interval!(interval_bitmap, MINOR_SIXTH);
-        weight += 3;
     }
 
-    //Sus
-    let mut sus = String::new();
+    //Sus, a suspended fourth replaces the major third, a minor chord becomes min(sus)
+    let mut suspension = None;
     if is_sus(interval_bitmap) {
-        if is_sus2(interval_bitmap) && !(is_major || is_minor) {
-            let sus2;
+        let mut sus = Suspension {
+            second: None,
+            fourth: None,
+        };
+        let altered = matches!(quality, Some(Quality::Diminished | Quality::Augmented));
+        if is_sus2(interval_bitmap)
+            && !altered
+            && !matches!(quality, Some(Quality::Major | Quality::Minor))
+        {
             if has_interval!(interval_bitmap, MAJOR_SECOND) {
-                sus2 = "2";
-                interval_bitmap = remove_interval!(interval_bitmap, MAJOR_SECOND);
-                weight += 4;
+                sus.second = Some(Second::Major);
+                remove_interval!(interval_bitmap, MAJOR_SECOND);
+                score.add(Component::Suspension, weights.suspension);
             } else {
-                sus2 = "♭2";
-                interval_bitmap = remove_interval!(interval_bitmap, MINOR_SECOND);
-                weight += 5;
+                sus.second = Some(Second::Minor);
+                remove_interval!(interval_bitmap, MINOR_SECOND);
+                score.add(Component::Suspension, weights.altered_suspension);
             }
             if is_sus4(interval_bitmap) {
                 if has_interval!(interval_bitmap, PERFECT_FOURTH) {
-                    sus = format!("sus({sus2}/4)");
-                    interval_bitmap = remove_interval!(interval_bitmap, PERFECT_FOURTH);
-                    weight += 4;
+                    sus.fourth = Some(Fourth::Perfect);
+                    remove_interval!(interval_bitmap, PERFECT_FOURTH);
+                    score.add(Component::Suspension, weights.suspension);
                 } else {
-                    sus = format!("sus({sus2}/#4)");
-                    interval_bitmap = remove_interval!(interval_bitmap, DIMINISHED_FIFTH);
-                    weight += 5;
-                }
-            } else {
-                sus = format!("sus{sus2}");
-                if !has_interval!(interval_bitmap, PERFECT_FIFTH) {
-                    weight += 3;
+                    sus.fourth = Some(Fourth::Augmented);
+                    remove_interval!(interval_bitmap, DIMINISHED_FIFTH);
+                    score.add(Component::Suspension, weights.altered_suspension);
                 }
+            } else if !has_interval!(interval_bitmap, PERFECT_FIFTH) {
+                score.add(Component::MissingFifth, weights.missing_fifth);
             }
-        } else if is_sus4(interval_bitmap) && !has_interval!(interval_bitmap, PERFECT_FIFTH) {
+        } else if is_sus4(interval_bitmap)
+            && !altered
+            && (quality.is_none() || !has_interval!(interval_bitmap, PERFECT_FIFTH))
+        {
             if has_interval!(interval_bitmap, PERFECT_FOURTH) {
-                sus = "sus4".to_string();
-                interval_bitmap = remove_interval!(interval_bitmap, PERFECT_FOURTH);
-                weight += 4;
+                sus.fourth = Some(Fourth::Perfect);
+                remove_interval!(interval_bitmap, PERFECT_FOURTH);
+                score.add(Component::Suspension, weights.suspension);
             } else {
-                sus = "sus#4".to_string();
-                interval_bitmap = remove_interval!(interval_bitmap, DIMINISHED_FIFTH);
-                weight += 5;
+                sus.fourth = Some(Fourth::Augmented);
+                remove_interval!(interval_bitmap, DIMINISHED_FIFTH);
+                score.add(Component::Suspension, weights.altered_suspension);
             }
         }
-        if is_minor {
-            quality = format!("min({sus})");
-            weight += 5;
-        } else {
-            quality = sus;
+        if sus.second.is_some() || sus.fourth.is_some() {
+            suspension = Some(sus);
+            match quality {
+                Some(Quality::Minor) => score.add(Component::Suspension, weights.minor_suspension),
+                _ => quality = Some(Quality::Suspended),
+            }
         }
     }
 
     if has_interval!(interval_bitmap, PERFECT_FIFTH) {
-        interval_bitmap = remove_interval!(interval_bitmap, PERFECT_FIFTH);
+        remove_interval!(interval_bitmap, PERFECT_FIFTH);
     }
 
     //Extensions
-    let mut extension = String::new();
-    if has_interval!(interval_bitmap, MAJOR_SEVENTH)
-        || has_interval!(interval_bitmap, MINOR_SEVENTH)
+    let seventh = if has_interval!(interval_bitmap, MAJOR_SEVENTH)
+        && !has_interval!(interval_bitmap, MINOR_SEVENTH)
     {
-        if is_min(interval_bitmap) {
-            if has_interval!(interval_bitmap, MAJOR_SEVENTH)
-                && !has_interval!(interval_bitmap, MINOR_SEVENTH)
-            {
-                extension += "(maj";
-                interval_bitmap = remove_interval!(interval_bitmap, MAJOR_SEVENTH);
-                weight += 5;
-            } else {
-                interval_bitmap = remove_interval!(interval_bitmap, MINOR_SEVENTH);
-                weight += 4;
-            }
-            if has_interval!(interval_bitmap, MAJOR_SECOND) {
-                if has_interval!(interval_bitmap, PERFECT_FOURTH) {
-                    if has_interval!(interval_bitmap, MAJOR_SIXTH) {
-                        extension += "13)";
-                        interval_bitmap = remove_interval!(interval_bitmap, MAJOR_SIXTH);
-                    } else {
-                        extension += "11)";
-                        interval_bitmap = remove_interval!(interval_bitmap, PERFECT_FOURTH);
-                    }
-                } else {
-                    extension += "9)";
-                    interval_bitmap = remove_interval!(interval_bitmap, MAJOR_SECOND);
-                }
-            } else {
-                extension += "7)";
-            }
-        } else {
-            if has_interval!(interval_bitmap, MAJOR_SEVENTH)
-                && !has_interval!(interval_bitmap, MINOR_SEVENTH)
-            {
-                extension += "maj";
-                interval_bitmap = remove_interval!(interval_bitmap, MAJOR_SEVENTH);
-                weight += 5;
-            } else {
-                interval_bitmap = remove_interval!(interval_bitmap, MINOR_SEVENTH);
-                weight += 4;
-            }
-            if has_interval!(interval_bitmap, MAJOR_SECOND) {
-                if has_interval!(interval_bitmap, PERFECT_FOURTH) {
-                    if has_interval!(interval_bitmap, MAJOR_SIXTH) {
-                        extension += "13";
-                        interval_bitmap = remove_interval!(interval_bitmap, MAJOR_SIXTH);
-                    } else {
-                        extension += "11";
-                    }
-                    interval_bitmap = remove_interval!(interval_bitmap, PERFECT_FOURTH);
+        remove_interval!(interval_bitmap, MAJOR_SEVENTH);
+        score.add(Component::Seventh, weights.major_seventh);
+        Some(Seventh::Major)
+    } else if has_interval!(interval_bitmap, MINOR_SEVENTH) {
+        remove_interval!(interval_bitmap, MINOR_SEVENTH);
+        score.add(Component::Seventh, weights.seventh);
+        Some(Seventh::Minor)
+    } else if quality == Some(Quality::Diminished) && has_interval!(interval_bitmap, MAJOR_SIXTH) {
+        remove_interval!(interval_bitmap, MAJOR_SIXTH);
+        score.add(Component::Seventh, weights.seventh);
+        Some(Seventh::Diminished)
+    } else {
+        None
+    };
+
+    let mut extension = None;
+    if let Some(seventh) = seventh {
+        let mut degree = 7;
+        if seventh != Seventh::Diminished && has_interval!(interval_bitmap, MAJOR_SECOND) {
+            if has_interval!(interval_bitmap, PERFECT_FOURTH) {
+                if has_interval!(interval_bitmap, MAJOR_SIXTH) {
+                    degree = 13;
+                    remove_interval!(interval_bitmap, MAJOR_SIXTH);
                 } else {
-                    extension += "9";
+                    degree = 11;
                 }
-                interval_bitmap = remove_interval!(interval_bitmap, MAJOR_SECOND);
+                remove_interval!(interval_bitmap, PERFECT_FOURTH);
             } else {
-                extension += "7";
+                degree = 9;
             }
+            remove_interval!(interval_bitmap, MAJOR_SECOND);
         }
+        extension = Some(Extension { seventh, degree });
     }
 
     //No matchs -> add
-    let mut add = String::new();
-    if has_interval!(interval_bitmap, MINOR_SECOND) {
-        add += "(♭9)";
-        interval_bitmap = remove_interval!(interval_bitmap, MINOR_SECOND);
-        weight += 7;
-    }
-    if has_interval!(interval_bitmap, MAJOR_SECOND) {
-        add += "(9)";
-        interval_bitmap = remove_interval!(interval_bitmap, MAJOR_SECOND);
-        weight += 6;
-    }
-    if has_interval!(interval_bitmap, MINOR_THIRD) {
-        add += "(#9)";
-        interval_bitmap = remove_interval!(interval_bitmap, MINOR_THIRD);
-        weight += 7;
-    }
-    if has_interval!(interval_bitmap, PERFECT_FOURTH) {
-        add += "(4)";
-        interval_bitmap = remove_interval!(interval_bitmap, PERFECT_FOURTH);
-        weight += 6;
-    }
-    if has_interval!(interval_bitmap, DIMINISHED_FIFTH) {
-        add += "(♭5)";
-        interval_bitmap = remove_interval!(interval_bitmap, DIMINISHED_FIFTH);
-        weight += 7;
-    }
-    if has_interval!(interval_bitmap, MINOR_SIXTH) {
-        add += "(♭13)";
-        interval_bitmap = remove_interval!(interval_bitmap, MINOR_SIXTH);
-        weight += 7;
-    }
-    if has_interval!(interval_bitmap, MAJOR_SIXTH) {
-        add += "(13)";
-        interval_bitmap = remove_interval!(interval_bitmap, MAJOR_SIXTH);
-        weight += 6;
-    }
-    if has_interval!(interval_bitmap, MINOR_SEVENTH) {
-        add += "(7)";
-        interval_bitmap = remove_interval!(interval_bitmap, MINOR_SEVENTH);
-        weight += 6;
-    }
-    if has_interval!(interval_bitmap, MAJOR_SEVENTH) {
-        add += "(maj7)";
-        interval_bitmap = remove_interval!(interval_bitmap, MAJOR_SEVENTH);
-        weight += 7;
+    let mut tensions = vec![];
+    let adds = [
+        (MINOR_SECOND, Tension::FlatNinth, weights.altered_tension),
+        (MAJOR_SECOND, Tension::Ninth, weights.added_tension),
+        (MINOR_THIRD, Tension::SharpNinth, weights.altered_tension),
+        (PERFECT_FOURTH, Tension::Fourth, weights.added_tension),
+        (
+            DIMINISHED_FIFTH,
+            Tension::FlatFifth,
+            weights.altered_tension,
+        ),
+        (
+            MINOR_SIXTH,
+            Tension::FlatThirteenth,
+            weights.altered_tension,
+        ),
+        (MAJOR_SIXTH, Tension::Thirteenth, weights.added_tension),
+        (MINOR_SEVENTH, Tension::MinorSeventh, weights.added_tension),
+        (
+            MAJOR_SEVENTH,
+            Tension::MajorSeventh,
+            weights.altered_tension,
+        ),
+    ];
+    for (interval, tension, points) in adds {
+        if has_interval!(interval_bitmap, interval) {
+            tensions.push(tension);
+            remove_interval!(interval_bitmap, interval);
+            score.add(Component::AddedTension, points);
+        }
     }
 
     //Return
-    let chord_name;
     if interval_bitmap != 0 {
         return None;
     }
-    if !quality.is_empty() || is_major {
-        chord_name = format!("{}{quality}{extension}{add}", root.name);
-    } else {
-        return None;
+
+    let mut chord = Chord::new(*root, quality?);
+    chord.suspension = suspension;
+    chord.extension = extension;
+    chord.tensions = tensions;
+    chord.score = score;
+
+    Some(chord)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, note::notes};
+
+    //Best chord of the keys, the lowest one as the bass
+    fn best(key_numbers: &[u8]) -> Chord {
+        let notes = notes(key_numbers);
+        let config = Config::default();
+        to_chord(&notes, &notes[0], &config.scoring).remove(0)
+    }
+
+    fn name(key_numbers: &[u8]) -> String {
+        Config::default().notation().chord(&best(key_numbers))
     }
 
-    Some((chord_name, weight))
+    fn figures(key_numbers: &[u8]) -> Option<(&'static str, &'static str)> {
+        let chord = best(key_numbers);
+        Some((chord.inversion()?.name(), chord.figured_bass()?))
+    }
+
+    #[test]
+    fn triads() {
+        assert_eq!(name(&[60, 64, 67]), "C");
+        assert_eq!(name(&[57, 60, 64]), "Amin");
+        assert_eq!(name(&[60, 67]), "C5");
+    }
+
+    #[test]
+    fn seventh_chords() {
+        assert_eq!(name(&[55, 59, 62, 65]), "G7");
+        assert_eq!(name(&[60, 64, 67, 71]), "Cmaj7");
+        assert_eq!(name(&[62, 65, 69, 72]), "Dmin7");
+        assert_eq!(name(&[60, 64, 67, 70, 74]), "C9");
+    }
+
+    #[test]
+    fn suspended_chords() {
+        assert_eq!(name(&[60, 62, 67]), "Csus2");
+        //A fourth without fifth replaces the major third
+        assert_eq!(name(&[60, 64, 65]), "Csus4");
+        assert_eq!(name(&[60, 65, 67]), "Csus4");
+    }
+
+    #[test]
+    fn altered_chords() {
+        assert_eq!(name(&[60, 63, 66]), "Cdim");
+        assert_eq!(name(&[60, 64, 68]), "Caug");
+        assert_eq!(name(&[60, 63, 66, 69]), "Cdim7");
+        assert_eq!(name(&[60, 63, 66, 70]), "Cmin7(♭5)");
+    }
+
+    #[test]
+    fn inversions_are_slash_chords() {
+        assert_eq!(name(&[64, 67, 72]), "C/E");
+        assert_eq!(name(&[53, 55, 59, 62]), "G7/F");
+    }
+
+    #[test]
+    fn triad_inversions_have_figures() {
+        assert_eq!(figures(&[60, 64, 67]), Some(("root", "5/3")));
+        assert_eq!(figures(&[64, 67, 72]), Some(("first", "6")));
+        assert_eq!(figures(&[67, 72, 76]), Some(("second", "6/4")));
+    }
+
+    #[test]
+    fn seventh_chord_inversions_have_figures() {
+        assert_eq!(figures(&[55, 59, 62, 65]), Some(("root", "7")));
+        assert_eq!(figures(&[59, 62, 65, 67]), Some(("first", "6/5")));
+        assert_eq!(figures(&[62, 65, 67, 71]), Some(("second", "4/3")));
+        assert_eq!(figures(&[53, 55, 59, 62]), Some(("third", "4/2")));
+    }
+
+    #[test]
+    fn suspended_chords_have_no_figures() {
+        assert!(best(&[60, 65, 67]).figured_bass().is_none());
+    }
 }
@@ -0,0 +1,377 @@
+use crate::{
+    arpeggio::ArpeggioMode,
+    error::{self, Error},
+    fretboard::Tuning,
+    midi::{ChannelRouting, PortSelection},
+    notation::{Notation, Style, Symbols},
+    note::{Key, Naming, Note},
+    output::Format,
+    scale::ScaleMode,
+    score::Scoring,
+    tempo::TimeSignature,
+    transpose::{self, Instrument},
+};
+use std::{
+    fmt::{self, Display},
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+pub type SharedConfig = Arc<Mutex<Config>>;
+
+#[derive(Clone)]
+pub struct Config {
+    pub style: Style,
+    pub naming: Naming,
+    pub symbols: Symbols,
+    pub format: Format,
+    pub scoring: Scoring,
+    pub explain: bool,
+    //Milliseconds
+    pub onset_window: u64,
+    pub release_hold: u64,
+    pub velocity_threshold: u8,
+    pub accent_velocity: u8,
+    pub channels: ChannelRouting,
+    pub ports: PortSelection,
+    //Keys below the split are played by the left hand
+    pub split: Option<Note>,
+    pub arpeggio: ArpeggioMode,
+    //Milliseconds
+    pub arpeggio_window: u64,
+    //Beats per minute when there is no MIDI clock, inferred from the chords when none
+    pub tempo: Option<u32>,
+    pub time_signature: TimeSignature,
+    pub scales: ScaleMode,
+    pub chord_scales: bool,
+    pub voice_leading: bool,
+    //Fingerings are shown for this tuning
+    pub fretboard: Option<Tuning>,
+    //Semitones added to the played keys, on top of the instrument
+    pub transpose: i8,
+    pub instrument: Instrument,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            style: Style::Classic,
+            naming: Naming::English,
+            symbols: Symbols::Style,
+            format: Format::Text,
+            scoring: Scoring::default(),
+            explain: false,
+            onset_window: 50,
+            release_hold: 100,
+            velocity_threshold: 0,
+            accent_velocity: 100,
+            channels: ChannelRouting::default(),
+            ports: PortSelection::Ask,
+            split: None,
+            arpeggio: ArpeggioMode::Off,
+            arpeggio_window: 500,
+            tempo: None,
+            time_signature: TimeSignature::default(),
+            scales: ScaleMode::Auto,
+            chord_scales: false,
+            voice_leading: false,
+            fretboard: None,
+            transpose: 0,
+            instrument: Instrument::Concert,
+        }
+    }
+}
+
+impl Config {
+    pub fn notation(&self) -> Notation {
+        Notation {
+            style: self.style,
+            naming: self.naming,
+            symbols: self.symbols,
+            key: self.scoring.key,
+        }
+    }
+
+    //Semitones from the played keys to the shown notes
+    pub fn transposition(&self) -> i8 {
+        self.transpose + self.instrument.shift()
+    }
+
+    //Parses "--option value" pairs from the command line
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Config, Error> {
+        let mut config = Config::default();
+
+        while let Some(arg) = args.next() {
+            let Some(option) = arg.strip_prefix("--") else {
+                return Err(format!("Unexpected argument \"{arg}\""))?;
+            };
+            match option.split_once('=') {
+                Some((option, value)) => config.set(option, value)?,
+                None => {
+                    let value = args
+                        .next()
+                        .ok_or(format!("Missing value for option \"{option}\""))?;
+                    config.set(option, &value)?;
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn set(&mut self, option: &str, value: &str) -> Result<(), Error> {
+        self.set_included(option, value, &mut vec![])
+    }
+
+    //Config files being loaded are kept in included, loading one of them again is an error
+    fn set_included(
+        &mut self,
+        option: &str,
+        value: &str,
+        included: &mut Vec<PathBuf>,
+    ) -> Result<(), Error> {
+        match option {
+            "style" => self.style = value.parse()?,
+            "naming" => self.naming = value.parse()?,
+            "accidentals" => self.symbols = value.parse()?,
+            "format" => self.format = value.parse()?,
+            "key" => {
+                self.scoring.key = match value {
+                    "none" => None,
+                    key => Some(Key::parse(key, self.naming)?),
+                }
+            }
+            "explain" => self.explain = parse_bool(value)?,
+            "onset_window" => self.onset_window = parse_millis(value)?,
+            "release_hold" => self.release_hold = parse_millis(value)?,
+            "velocity_threshold" => self.velocity_threshold = parse_velocity(value)?,
+            "accent_velocity" => self.accent_velocity = parse_velocity(value)?,
+            "channels" => self.channels.set_filter(value)?,
+            "channel_map" => self.channels.set_map(value)?,
+            "channel_mode" => self.channels.mode = value.parse()?,
+            "ports" => self.ports = value.parse()?,
+            "split" => {
+                self.split = match value {
+                    "none" => None,
+                    note => Some(Note::parse(note, self.naming, self.scoring.key.as_ref())?),
+                }
+            }
+            "arpeggio" => self.arpeggio = value.parse()?,
+            "arpeggio_window" => self.arpeggio_window = parse_millis(value)?,
+            "tempo" => {
+                self.tempo = match value {
+                    "auto" => None,
+                    bpm => Some(parse_bpm(bpm)?),
+                }
+            }
+            "time_signature" => self.time_signature = value.parse()?,
+            "scales" => self.scales = value.parse()?,
+            "chord_scales" => self.chord_scales = parse_bool(value)?,
+            "voice_leading" => self.voice_leading = parse_bool(value)?,
+            "transpose" => self.transpose = transpose::parse_transpose(value)?,
+            "instrument" => self.instrument = value.parse()?,
+            "fretboard" => {
+                self.fretboard = match value {
+                    "none" => None,
+                    tuning => Some(Tuning::parse(tuning, self.naming)?),
+                }
+            }
+            "config" => self.load(value, included)?,
+            _ => match option.strip_prefix("weight.") {
+                Some(weight) => self.scoring.weights.set(weight, value)?,
+                None => return Err(format!("Unknown option \"{option}\""))?,
+            },
+        }
+        Ok(())
+    }
+
+    //Config files have one "option = value" per line, '#' starts a comment
+    fn load(&mut self, path: &str, included: &mut Vec<PathBuf>) -> Result<(), Error> {
+        let canonical = fs::canonicalize(path).map_err(error::file_error(path))?;
+        if included.contains(&canonical) {
+            return Err(format!("{path}: config file included by itself"))?;
+        }
+        let content = fs::read_to_string(path).map_err(error::file_error(path))?;
+        included.push(canonical);
+
+        for (i, line) in content.lines().enumerate() {
+            let line = match line.split_once('#') {
+                Some((line, _)) => line.trim(),
+                None => line.trim(),
+            };
+            if line.is_empty() {
+                continue;
+            }
+            let Some((option, value)) = line.split_once('=') else {
+                return Err(format!("{path}:{}: expected \"option = value\"", i + 1))?;
+            };
+            self.set_included(option.trim(), value.trim(), included)
+                .map_err(|e| match e {
+                    Error::Parse(e) => Error::Parse(format!("{path}:{}: {e}", i + 1)),
+                    e => e,
+                })?;
+        }
+
+        included.pop();
+        Ok(())
+    }
+}
+
+fn parse_millis(value: &str) -> Result<u64, String> {
+    value
+        .trim_end_matches("ms")
+        .parse()
+        .map_err(|_| format!("Invalid duration \"{value}\", expected milliseconds"))
+}
+
+fn parse_bpm(value: &str) -> Result<u32, String> {
+    match value.trim_end_matches("bpm").parse() {
+        Ok(bpm @ 20..=400) => Ok(bpm),
+        _ => Err(format!(
+            "Invalid tempo \"{value}\", expected auto or 20 to 400 bpm"
+        )),
+    }
+}
+
+fn parse_velocity(value: &str) -> Result<u8, String> {
+    match value.parse() {
+        Ok(velocity) if velocity < 128 => Ok(velocity),
+        _ => Err(format!("Invalid velocity \"{value}\", expected 0 to 127")),
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "on" | "true" | "yes" | "1" => Ok(true),
+        "off" | "false" | "no" | "0" => Ok(false),
+        _ => Err(format!("Invalid value \"{value}\", expected on or off")),
+    }
+}
+
+impl Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let notation = self.notation();
+        writeln!(f, "\tstyle : {}", self.style)?;
+        writeln!(f, "\tnaming : {}", self.naming)?;
+        writeln!(f, "\taccidentals : {}", self.symbols)?;
+        writeln!(f, "\tformat : {}", self.format)?;
+        match &self.scoring.key {
+            Some(key) => writeln!(f, "\tkey : {}", notation.key(key))?,
+            None => writeln!(f, "\tkey : none")?,
+        }
+        writeln!(f, "\texplain : {}", if self.explain { "on" } else { "off" })?;
+        writeln!(f, "\tonset_window : {}ms", self.onset_window)?;
+        writeln!(f, "\trelease_hold : {}ms", self.release_hold)?;
+        writeln!(f, "\tvelocity_threshold : {}", self.velocity_threshold)?;
+        writeln!(f, "\taccent_velocity : {}", self.accent_velocity)?;
+        writeln!(f, "{}", self.channels)?;
+        writeln!(f, "\tports : {}", self.ports)?;
+        match &self.split {
+            Some(split) => writeln!(f, "\tsplit : {}", notation.note(split))?,
+            None => writeln!(f, "\tsplit : none")?,
+        }
+        writeln!(f, "\tarpeggio : {}", self.arpeggio)?;
+        writeln!(f, "\tarpeggio_window : {}ms", self.arpeggio_window)?;
+        match self.tempo {
+            Some(bpm) => writeln!(f, "\ttempo : {bpm}bpm")?,
+            None => writeln!(f, "\ttempo : auto")?,
+        }
+        writeln!(f, "\ttime_signature : {}", self.time_signature)?;
+        writeln!(f, "\tscales : {}", self.scales)?;
+        writeln!(
+            f,
+            "\tchord_scales : {}",
+            if self.chord_scales { "on" } else { "off" }
+        )?;
+        writeln!(
+            f,
+            "\tvoice_leading : {}",
+            if self.voice_leading { "on" } else { "off" }
+        )?;
+        match &self.fretboard {
+            Some(tuning) => writeln!(f, "\tfretboard : {}", tuning.name(&notation))?,
+            None => writeln!(f, "\tfretboard : none")?,
+        }
+        writeln!(f, "\ttranspose : {:+}", self.transpose)?;
+        writeln!(f, "\tinstrument : {}", self.instrument)?;
+        write!(f, "\tweight : {}", self.scoring.weights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_shown_in_the_naming() {
+        let mut config = Config::default();
+        config.set("naming", "german").unwrap();
+        config.set("key", "Asmin").unwrap();
+        config.set("split", "Fis3").unwrap();
+        config.set("fretboard", "E2,A2,D3,G3,H3,E4").unwrap();
+        let settings = config.to_string();
+        assert!(settings.contains("key : As minor"));
+        assert!(settings.contains("split : Fis3"));
+        assert!(settings.contains("fretboard : E2,A2,D3,G3,H3,E4"));
+    }
+
+    #[test]
+    fn config_files_including_each_other_fail() {
+        let dir = std::env::temp_dir().join(format!("midi2chord-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("first.conf");
+        let second = dir.join("second.conf");
+        fs::write(
+            &first,
+            format!("style = jazz\nconfig = {}\n", second.display()),
+        )
+        .unwrap();
+        fs::write(&second, format!("config = {}\n", first.display())).unwrap();
+
+        let mut config = Config::default();
+        let result = config.set("config", first.to_str().unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+        match result {
+            Err(Error::Parse(e)) => assert!(e.contains("included by itself")),
+            _ => panic!("the include cycle was not reported"),
+        }
+    }
+
+    #[test]
+    fn included_config_files_are_loaded() {
+        let dir = std::env::temp_dir().join(format!("midi2chord-include-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("first.conf");
+        let second = dir.join("second.conf");
+        fs::write(
+            &first,
+            format!(
+                "config = {}\nconfig = {}\n",
+                second.display(),
+                second.display()
+            ),
+        )
+        .unwrap();
+        fs::write(&second, "style = jazz\n").unwrap();
+
+        let mut config = Config::default();
+        let result = config.set("config", first.to_str().unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(result.is_ok());
+        assert!(config.style == Style::Jazz);
+    }
+
+    #[test]
+    fn invalid_settings_are_parse_errors() {
+        let mut config = Config::default();
+        for (option, value) in [
+            ("key", "X"),
+            ("split", "C#x"),
+            ("fretboard", "E2,A2"),
+            ("channels", "17"),
+        ] {
+            assert!(matches!(config.set(option, value), Err(Error::Parse(_))));
+        }
+    }
+}
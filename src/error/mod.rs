@@ -0,0 +1,80 @@
+use midir::{ConnectError, InitError, PortInfoError};
+use std::{
+    fmt::{self, Display},
+    io,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    NoPorts,
+    InvalidSelection(String),
+    Connection(String),
+    Parse(String),
+    Io(io::Error),
+}
+
+impl Error {
+    //Exit code of the program when it stops on this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Parse(_) => 2,
+            Error::NoPorts => 3,
+            Error::InvalidSelection(_) => 4,
+            Error::Connection(_) => 5,
+            Error::Io(_) => 6,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoPorts => write!(
+                f,
+                "No midi port available, plug in a keyboard or use \"--ports virtual\""
+            ),
+            Error::InvalidSelection(e) => write!(f, "{e}"),
+            Error::Connection(e) => write!(f, "Midi connection failed: {e}"),
+            Error::Parse(e) => write!(f, "{e}"),
+            Error::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+//I/O errors name the file they happened on
+pub fn file_error(path: &str) -> impl FnOnce(io::Error) -> Error + '_ {
+    move |e| Error::Io(io::Error::new(e.kind(), format!("{path}: {e}")))
+}
+
+//Parse errors of the FromStr implementations are plain messages
+impl From<String> for Error {
+    fn from(e: String) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<InitError> for Error {
+    fn from(e: InitError) -> Self {
+        Error::Connection(e.to_string())
+    }
+}
+
+impl From<PortInfoError> for Error {
+    fn from(e: PortInfoError) -> Self {
+        Error::Connection(e.to_string())
+    }
+}
+
+impl<T> From<ConnectError<T>> for Error {
+    fn from(e: ConnectError<T>) -> Self {
+        Error::Connection(e.to_string())
+    }
+}
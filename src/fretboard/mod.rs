@@ -0,0 +1,364 @@
+use crate::{
+    chord::Chord,
+    error::Error,
+    notation::Notation,
+    note::{Naming, Note},
+};
+
+//Highest fret a fingering can reach
+const MAX_FRET: u8 = 12;
+
+//Frets under the hand without moving it
+const HAND_SPAN: u8 = 4;
+
+const FINGERS: usize = 4;
+
+//Fingerings shown for a chord
+const MAX_FINGERINGS: usize = 3;
+
+//The search tries every fret of every string, more strings would take too long
+const STRINGS: std::ops::RangeInclusive<usize> = 3..=8;
+
+const PRESETS: [(&str, &str); 4] = [
+    ("guitar", "E2,A2,D3,G3,B3,E4"),
+    ("drop_d", "D2,A2,D3,G3,B3,E4"),
+    ("ukulele", "G4,C4,E4,A4"),
+    ("bass", "E1,A1,D2,G2"),
+];
+
+//Open strings from the lowest one as written on a chord box, with the preset name if any
+#[derive(Clone, PartialEq)]
+pub struct Tuning {
+    name: Option<&'static str>,
+    strings: Vec<Note>,
+}
+
+//Fret of each string, None for a muted one and 0 for an open one
+#[derive(Clone, PartialEq)]
+pub struct Fingering {
+    pub frets: Vec<Option<u8>>,
+}
+
+impl Fingering {
+    fn fretted(&self) -> impl Iterator<Item = u8> + '_ {
+        self.frets
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|fret| *fret > 0)
+    }
+
+    //Lowest fretted fret, 0 when every string is open or muted
+    pub fn position(&self) -> u8 {
+        self.fretted().min().unwrap_or(0)
+    }
+
+    //Frets between the lowest and the highest finger
+    pub fn stretch(&self) -> u8 {
+        self.fretted().max().unwrap_or(0) - self.position()
+    }
+
+    //Strings held at the lowest fret are barred by one finger
+    fn fingers(&self) -> usize {
+        let position = self.position();
+        let above = self.fretted().filter(|fret| *fret > position).count();
+        let barre = self.fretted().any(|fret| fret == position) as usize;
+        above + barre
+    }
+
+    fn muted(&self) -> usize {
+        self.frets.iter().filter(|fret| fret.is_none()).count()
+    }
+
+    //Lower is easier: a short stretch close to the nut, few muted strings
+    fn difficulty(&self) -> u32 {
+        self.stretch() as u32 * 2
+            + self.position().saturating_sub(1) as u32
+            + self.muted() as u32 * 3
+    }
+
+    //"x32010", frets above 9 are separated: "x-10-12-11-12-x"
+    pub fn compact(&self) -> String {
+        let frets: Vec<String> = self
+            .frets
+            .iter()
+            .map(|fret| fret.map_or("x".to_string(), |fret| fret.to_string()))
+            .collect();
+        match self.frets.iter().flatten().any(|fret| *fret > 9) {
+            true => frets.join("-"),
+            false => frets.concat(),
+        }
+    }
+
+    //Chord box, strings from the lowest on the left and frets going down:
+    //  x     o   o
+    //  ===========
+    //1 | | | | o |
+    //2 | | o | | |
+    //3 | o | | | |
+    pub fn diagram(&self) -> Vec<String> {
+        //Shapes within reach of the nut are drawn from it
+        let first = match self.position() + self.stretch() <= HAND_SPAN {
+            true => 1,
+            false => self.position(),
+        };
+        let rows = (self.position() + self.stretch() + 1 - first).max(HAND_SPAN);
+        let width = self.frets.len() * 2 - 1;
+
+        let markers: Vec<&str> = self
+            .frets
+            .iter()
+            .map(|fret| match fret {
+                None => "x",
+                Some(0) => "o",
+                Some(_) => " ",
+            })
+            .collect();
+        let mut lines = vec![format!("   {}", markers.join(" ").trim_end())];
+        lines.push(match first {
+            1 => format!("   {}", "=".repeat(width)),
+            _ => format!("   {}", "-".repeat(width)),
+        });
+        for fret in first..first + rows {
+            let strings: Vec<&str> = self
+                .frets
+                .iter()
+                .map(|played| match played {
+                    Some(played) if *played == fret => "o",
+                    _ => "|",
+                })
+                .collect();
+            lines.push(format!("{fret:>2} {}", strings.join(" ")));
+        }
+        lines
+    }
+}
+
+impl Tuning {
+    //Re-entrant tunings (ukulele) do not have their lowest note on the first string
+    fn is_ascending(&self) -> bool {
+        self.strings
+            .windows(2)
+            .all(|pair| u8::from(&pair[0]) < u8::from(&pair[1]))
+    }
+
+    fn sounding(&self, frets: &[Option<u8>]) -> Vec<u8> {
+        self.strings
+            .iter()
+            .zip(frets)
+            .filter_map(|(string, fret)| fret.map(|fret| u8::from(string) + fret))
+            .collect()
+    }
+
+    //Every way to play the chord within a hand span, the easiest first
+    pub fn fingerings(&self, chord: &Chord) -> Vec<Fingering> {
+        let intervals = chord.intervals();
+        let bass = chord.bass.unwrap_or(chord.root).pitch_class();
+        //The fifth can be left out once there are four notes or more than strings
+        let fifth = 0b1 << 7;
+        let optional = match intervals.count_ones() >= 4
+            || intervals.count_ones() as usize > self.strings.len()
+        {
+            true => intervals & fifth,
+            false => 0,
+        };
+        let interval = |key_number: u8| (12 + key_number % 12 - chord.root.pitch_class()) % 12;
+        let in_chord = |key_number: u8| intervals & (0b1 << interval(key_number)) != 0;
+
+        let mut fingerings: Vec<Fingering> = vec![];
+        for start in 1..=MAX_FRET + 1 - HAND_SPAN {
+            //Frets each string can play in this hand position, muted strings first
+            let choices: Vec<Vec<Option<u8>>> = self
+                .strings
+                .iter()
+                .map(|string| {
+                    let open = u8::from(string);
+                    let mut frets = vec![None];
+                    frets.extend(
+                        std::iter::once(0)
+                            .chain(start..start + HAND_SPAN)
+                            .filter(|fret| in_chord(open + fret))
+                            .map(Some),
+                    );
+                    frets
+                })
+                .collect();
+
+            let mut frets = vec![None; self.strings.len()];
+            self.search(&choices, 0, &mut frets, &mut |frets| {
+                let sounding = self.sounding(frets);
+                let played = sounding
+                    .iter()
+                    .fold(0u16, |set, key| set | (0b1 << interval(*key)));
+                if (intervals & !optional) & !played != 0 {
+                    return;
+                }
+                //Muted strings only on the edges, the lowest note is the bass on ascending tunings
+                let first = frets.iter().position(|fret| fret.is_some());
+                let last = frets.iter().rposition(|fret| fret.is_some());
+                if let (Some(first), Some(last)) = (first, last) {
+                    if frets[first..=last].iter().any(|fret| fret.is_none()) {
+                        return;
+                    }
+                }
+                let lowest = sounding.iter().min().map(|key| key % 12);
+                if self.is_ascending() && lowest != Some(bass) {
+                    return;
+                }
+                let fingering = Fingering {
+                    frets: frets.to_vec(),
+                };
+                if fingering.fingers() <= FINGERS
+                    && fingering.stretch() < HAND_SPAN
+                    && !fingerings.contains(&fingering)
+                {
+                    fingerings.push(fingering);
+                }
+            });
+        }
+
+        fingerings.sort_by_key(|fingering| fingering.difficulty());
+        fingerings.truncate(MAX_FINGERINGS);
+        fingerings
+    }
+
+    //Tries each choice of each string in turn
+    fn search(
+        &self,
+        choices: &[Vec<Option<u8>>],
+        string: usize,
+        frets: &mut Vec<Option<u8>>,
+        found: &mut dyn FnMut(&[Option<u8>]),
+    ) {
+        if string == choices.len() {
+            found(frets);
+            return;
+        }
+        for choice in &choices[string] {
+            frets[string] = *choice;
+            self.search(choices, string + 1, frets, found);
+        }
+    }
+}
+
+impl Tuning {
+    //A preset name or the open strings from the lowest one: "D2,A2,D3,G3,B3,E4", movable-do
+    //strings are read in fixed-do
+    pub fn parse(s: &str, naming: Naming) -> Result<Tuning, Error> {
+        let (name, strings, naming) = match PRESETS.iter().find(|(name, _)| *name == s) {
+            Some((name, strings)) => (Some(*name), *strings, Naming::English),
+            None if naming == Naming::MovableDo => (None, s, Naming::FixedDo),
+            None => (None, s, naming),
+        };
+        let strings = strings
+            .split(',')
+            .map(|note| Note::parse(note, naming, None))
+            .collect::<Result<Vec<Note>, Error>>()
+            .map_err(|e| {
+                Error::Parse(format!(
+                    "{e} (tunings: guitar, drop_d, ukulele, bass or notes like E2,A2,D3,G3,B3,E4)"
+                ))
+            })?;
+        if !STRINGS.contains(&strings.len()) {
+            return Err(Error::Parse(format!(
+                "Tuning \"{s}\" needs from {} to {} strings",
+                STRINGS.start(),
+                STRINGS.end()
+            )));
+        }
+        Ok(Tuning { name, strings })
+    }
+}
+
+impl Tuning {
+    //The preset name or the open strings as they are read, movable-do ones in fixed-do
+    pub fn name(&self, notation: &Notation) -> String {
+        match self.name {
+            Some(name) => name.to_string(),
+            None => {
+                let notation = Notation {
+                    key: None,
+                    ..*notation
+                };
+                let strings: Vec<String> = self
+                    .strings
+                    .iter()
+                    .map(|note| notation.note(note))
+                    .collect();
+                strings.join(",")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chord, config::Config, note::notes};
+
+    fn chord(key_numbers: &[u8]) -> Chord {
+        let notes = notes(key_numbers);
+        chord::to_chord(&notes, &notes[0], &Config::default().scoring).remove(0)
+    }
+
+    fn compact(tuning: &str, key_numbers: &[u8]) -> Vec<String> {
+        Tuning::parse(tuning, Naming::English)
+            .unwrap()
+            .fingerings(&chord(key_numbers))
+            .iter()
+            .map(Fingering::compact)
+            .collect()
+    }
+
+    #[test]
+    fn open_chords_come_first() {
+        assert_eq!(compact("guitar", &[48, 52, 55])[0], "x32010");
+        assert_eq!(compact("guitar", &[45, 48, 52])[0], "x02210");
+        assert_eq!(compact("drop_d", &[50, 54, 57])[0], "000232");
+        assert_eq!(compact("ukulele", &[48, 52, 55])[0], "0003");
+    }
+
+    #[test]
+    fn high_frets_are_separated() {
+        let fingering = Fingering {
+            frets: vec![None, Some(10), Some(12), Some(12), Some(12), Some(10)],
+        };
+        assert_eq!(fingering.compact(), "x-10-12-12-12-10");
+        assert_eq!(fingering.position(), 10);
+        assert_eq!(fingering.stretch(), 2);
+    }
+
+    #[test]
+    fn chord_box_is_drawn_from_the_nut() {
+        let fingerings = Tuning::parse("guitar", Naming::English)
+            .unwrap()
+            .fingerings(&chord(&[48, 52, 55]));
+        assert_eq!(
+            fingerings[0].diagram(),
+            [
+                "   x     o   o",
+                "   ===========",
+                " 1 | | | | o |",
+                " 2 | | o | | |",
+                " 3 | o | | | |",
+                " 4 | | | | | |",
+            ]
+        );
+    }
+
+    #[test]
+    fn tunings_are_presets_or_strings() {
+        let notation = Config::default().notation();
+        let tuning = Tuning::parse("D2,A2,D3,G3,A3,D4", Naming::English).unwrap();
+        assert_eq!(tuning.name(&notation), "D2,A2,D3,G3,A3,D4");
+        assert_eq!(
+            Tuning::parse("drop_d", Naming::German)
+                .unwrap()
+                .name(&notation),
+            "drop_d"
+        );
+        assert!(Tuning::parse("E2,A2", Naming::English).is_err());
+        assert!(Tuning::parse("B1,E2,A2,D3,G3,B3,E4,A4,D5", Naming::English).is_err());
+        assert!(Tuning::parse("banjo", Naming::English).is_err());
+    }
+}
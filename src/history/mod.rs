@@ -0,0 +1,117 @@
+use crate::{chord::Chord, notation::Notation, note::Note, tempo::Position};
+
+//A chord, the notes it was named from and when it started
+#[derive(Clone)]
+pub struct Moment {
+    pub stamp: u64,
+    pub position: Option<Position>,
+    pub notes: Vec<Note>,
+    pub chord: Chord,
+}
+
+//Named chords of the session, in the order they were played
+#[derive(Clone, Default)]
+pub struct History {
+    moments: Vec<Moment>,
+}
+
+impl History {
+    //A chord repeated under the same name is only kept once, even when a released key
+    //changed its score
+    pub fn push(&mut self, moment: Moment) {
+        if self
+            .moments
+            .last()
+            .is_some_and(|last| last.chord.is_same(&moment.chord))
+        {
+            return;
+        }
+        self.moments.push(moment);
+    }
+
+    //Chords played before the tempo was known get their position once it is
+    pub fn locate(&mut self, position_at: impl Fn(u64) -> Option<Position>) {
+        for moment in self
+            .moments
+            .iter_mut()
+            .filter(|moment| moment.position.is_none())
+        {
+            moment.position = position_at(moment.stamp);
+        }
+    }
+
+    pub fn moments(&self) -> &[Moment] {
+        &self.moments
+    }
+
+    //One line per chord: "bar 2 beat 1 (120 bpm) : Dm7", or the time when the tempo is unknown
+    pub fn describe(&self, notation: &Notation) -> String {
+        if self.moments.is_empty() {
+            return "-".to_string();
+        }
+
+        let first_stamp = self.moments[0].stamp;
+        let lines: Vec<String> = self
+            .moments
+            .iter()
+            .map(|moment| match &moment.position {
+                Some(position) => format!("{position} : {}", notation.chord(&moment.chord)),
+                None => format!(
+                    "{:.2}s : {}",
+                    (moment.stamp - first_stamp) as f64 / 1_000_000.0,
+                    notation.chord(&moment.chord)
+                ),
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chord, config::Config, note::notes};
+
+    fn moment(stamp: u64, position: Option<Position>, key_numbers: &[u8]) -> Moment {
+        let notes = notes(key_numbers);
+        let chord = chord::to_chord(&notes, &notes[0], &Config::default().scoring).remove(0);
+        Moment {
+            stamp,
+            position,
+            notes,
+            chord,
+        }
+    }
+
+    #[test]
+    fn repeated_chord_is_kept_once() {
+        let mut history = History::default();
+        assert_eq!(history.describe(&Config::default().notation()), "-");
+        history.push(moment(0, None, &[60, 64, 67]));
+        history.push(moment(500_000, None, &[60, 64, 67]));
+        history.push(moment(1_000_000, None, &[60, 64]));
+        history.push(moment(1_500_000, None, &[55, 59, 62, 65]));
+        assert_eq!(
+            history.describe(&Config::default().notation()),
+            "0.00s : C\n1.50s : G7"
+        );
+    }
+
+    #[test]
+    fn chords_are_placed_in_the_bar() {
+        let mut history = History::default();
+        let position = |bar, beat| {
+            Some(Position {
+                bar,
+                beat,
+                bpm: 120,
+            })
+        };
+        history.push(moment(0, position(1, 1), &[62, 65, 69, 72]));
+        history.push(moment(2_000_000, position(2, 1), &[55, 59, 62, 65]));
+        assert_eq!(
+            history.describe(&Config::default().notation()),
+            "bar 1 beat 1 (120 bpm) : Dmin7\nbar 2 beat 1 (120 bpm) : G7"
+        );
+    }
+}
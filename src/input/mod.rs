@@ -0,0 +1,349 @@
+use crate::{
+    error::{self, Error},
+    smf,
+};
+use midir::{Ignore, MidiInput, MidiInputConnection};
+use std::{
+    fs,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+//Receives the stamp (in microseconds) and the raw bytes of each message
+pub type Handler = Box<dyn FnMut(u64, &[u8]) + Send>;
+
+//Anything the keyboard can receive midi messages from
+pub trait InputSource {
+    fn name(&self) -> String;
+
+    //Live stamps start at the connection, they are shifted to line up with the other sources
+    fn is_live(&self) -> bool {
+        true
+    }
+
+    //Messages are sent to the handler until the connection is dropped
+    fn connect(self: Box<Self>, handler: Handler) -> Result<Connection, Error>;
+}
+
+pub enum Connection {
+    //Held to keep the port open
+    Midi(#[allow(dead_code)] MidiInputConnection<Handler>),
+    //The replay is stopped once dropped
+    Replay {
+        stop: Arc<AtomicBool>,
+        thread: Option<JoinHandle<()>>,
+    },
+}
+
+impl Connection {
+    //Waits until every message was sent, live connections never end
+    pub fn join(mut self) -> Result<(), Error> {
+        if let Connection::Replay { thread, .. } = &mut self {
+            if let Some(thread) = thread.take() {
+                return thread
+                    .join()
+                    .map_err(|_| Error::Connection("Replay stopped on a panic".to_string()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Connection::Replay { stop, .. } = self {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+fn midi_input() -> Result<MidiInput, Error> {
+    let mut midi_in = MidiInput::new("Keyboard")?;
+    midi_in.ignore(Ignore::None);
+    Ok(midi_in)
+}
+
+//ALSA ends port names with the client and port numbers ("Piano:Piano MIDI 1 20:0"), a keyboard
+//plugged back usually gets another client number so ports are told apart without them
+pub fn port_key(name: &str) -> &str {
+    let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    match name.rsplit_once(' ') {
+        Some((key, ids))
+            if ids
+                .split_once(':')
+                .is_some_and(|(client, port)| is_number(client) && is_number(port)) =>
+        {
+            key
+        }
+        _ => name,
+    }
+}
+
+//A hardware (or software) port, found by its name without the ALSA numbers when connecting
+pub struct MidiPort {
+    name: String,
+    key: String,
+}
+
+impl MidiPort {
+    pub fn new(name: &str) -> MidiPort {
+        MidiPort {
+            name: name.to_string(),
+            key: port_key(name).to_string(),
+        }
+    }
+}
+
+impl InputSource for MidiPort {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn connect(self: Box<Self>, handler: Handler) -> Result<Connection, Error> {
+        let midi_in = midi_input()?;
+
+        let port = midi_in
+            .ports()
+            .into_iter()
+            .find(|port| {
+                midi_in
+                    .port_name(port)
+                    .is_ok_and(|name| port_key(&name) == self.key)
+            })
+            .ok_or(Error::Connection(format!(
+                "Port \"{}\" disappeared",
+                self.name
+            )))?;
+
+        let connection = midi_in.connect(
+            &port,
+            "midir-in",
+            |stamp, message, handler: &mut Handler| handler(stamp, message),
+            handler,
+        )?;
+        Ok(Connection::Midi(connection))
+    }
+}
+
+//A port created by the program that other applications can send to (ALSA, CoreMIDI, JACK)
+pub struct VirtualPort {
+    name: String,
+}
+
+impl VirtualPort {
+    pub fn new(name: &str) -> VirtualPort {
+        VirtualPort {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl InputSource for VirtualPort {
+    fn name(&self) -> String {
+        format!("{} (virtual)", self.name)
+    }
+
+    #[cfg(unix)]
+    fn connect(self: Box<Self>, handler: Handler) -> Result<Connection, Error> {
+        use midir::os::unix::VirtualInput;
+
+        let connection = midi_input()?.create_virtual(
+            &self.name,
+            |stamp, message, handler: &mut Handler| handler(stamp, message),
+            handler,
+        )?;
+        Ok(Connection::Midi(connection))
+    }
+
+    #[cfg(not(unix))]
+    fn connect(self: Box<Self>, _handler: Handler) -> Result<Connection, Error> {
+        Err(Error::Connection(
+            "Virtual ports are not available on this system".to_string(),
+        ))
+    }
+}
+
+//Messages given in memory or read from a file, sent as fast as possible or at their recorded pace
+pub struct Replay {
+    name: String,
+    messages: Vec<(u64, Vec<u8>)>,
+    realtime: bool,
+}
+
+impl Replay {
+    pub fn new(name: &str, messages: Vec<(u64, Vec<u8>)>) -> Replay {
+        Replay {
+            name: name.to_string(),
+            messages,
+            realtime: false,
+        }
+    }
+
+    pub fn realtime(mut self, realtime: bool) -> Replay {
+        self.realtime = realtime;
+        self
+    }
+
+    //One message per line: the stamp in microseconds then the bytes in hexadecimal ("1520 90 3c 64"),
+    //or a standard midi file
+    pub fn from_file(path: &str) -> Result<Replay, Error> {
+        let bytes = fs::read(path).map_err(error::file_error(path))?;
+        if smf::is_smf(&bytes) {
+            let messages = smf::parse(&bytes).map_err(|e| Error::Parse(format!("{path}: {e}")))?;
+            return Ok(Replay::new(path, messages));
+        }
+        let content = String::from_utf8(bytes)
+            .map_err(|_| Error::Parse(format!("{path}: not a replay nor a midi file")))?;
+
+        let mut messages = vec![];
+        for (i, line) in content.lines().enumerate() {
+            let line = match line.split_once('#') {
+                Some((line, _)) => line.trim(),
+                None => line.trim(),
+            };
+            if line.is_empty() {
+                continue;
+            }
+            let message =
+                parse_message(line).map_err(|e| Error::Parse(format!("{path}:{}: {e}", i + 1)))?;
+            messages.push(message);
+        }
+
+        Ok(Replay::new(path, messages))
+    }
+}
+
+//Line of a replay file
+pub fn format_message(stamp: u64, message: &[u8]) -> String {
+    let bytes: Vec<String> = message.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!("{stamp} {}", bytes.join(" "))
+}
+
+//A status byte (high bit set) followed by data bytes of 7 bits
+pub fn is_valid_message(message: &[u8]) -> bool {
+    match message.split_first() {
+        Some((status, data)) => *status >= 0x80 && data.iter().all(|byte| *byte < 0x80),
+        None => false,
+    }
+}
+
+fn parse_message(line: &str) -> Result<(u64, Vec<u8>), Error> {
+    let mut fields = line.split_whitespace();
+    let stamp = fields
+        .next()
+        .and_then(|stamp| stamp.parse().ok())
+        .ok_or(Error::Parse(format!("Invalid stamp in \"{line}\"")))?;
+
+    let mut bytes = vec![];
+    for field in fields {
+        let byte = u8::from_str_radix(field, 16)
+            .map_err(|_| Error::Parse(format!("Invalid byte \"{field}\", expected hexadecimal")))?;
+        bytes.push(byte);
+    }
+    if bytes.is_empty() {
+        return Err(Error::Parse(format!("Missing message bytes in \"{line}\"")));
+    }
+    if !is_valid_message(&bytes) {
+        return Err(Error::Parse(format!(
+            "Invalid message in \"{line}\", expected a status byte from 80 then data bytes below 80"
+        )));
+    }
+
+    Ok((stamp, bytes))
+}
+
+impl InputSource for Replay {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn is_live(&self) -> bool {
+        false
+    }
+
+    fn connect(self: Box<Self>, mut handler: Handler) -> Result<Connection, Error> {
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let stopped = stop.clone();
+        let thread = thread::spawn(move || {
+            let start = Instant::now();
+            let first_stamp = self.messages.first().map_or(0, |(stamp, _)| *stamp);
+
+            for (stamp, message) in &self.messages {
+                if self.realtime {
+                    let due = Duration::from_micros(stamp.saturating_sub(first_stamp));
+                    thread::sleep(due.saturating_sub(start.elapsed()));
+                }
+                if stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+                handler(*stamp, message);
+            }
+        });
+
+        Ok(Connection::Replay {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    //Messages the source sends, in order, until it is done
+    fn received(source: Replay) -> Vec<(u64, Vec<u8>)> {
+        let (sender, receiver) = mpsc::channel();
+        let _connection = Box::new(source)
+            .connect(Box::new(move |stamp, message| {
+                sender.send((stamp, message.to_vec())).unwrap()
+            }))
+            .unwrap();
+        receiver.iter().collect()
+    }
+
+    #[test]
+    fn replay_sends_the_messages_in_order() {
+        let messages = vec![(0, vec![0x90, 60, 80]), (1000, vec![0x80, 60, 0])];
+        assert_eq!(received(Replay::new("test", messages.clone())), messages);
+    }
+
+    #[test]
+    fn replay_file_is_read() {
+        let path = std::env::temp_dir().join("midi2chord_replay_test.txt");
+        fs::write(&path, "# C major\n0 90 3c 64\n1000 90 40 50 # third\n").unwrap();
+        let source = Replay::from_file(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            received(source.unwrap()),
+            [(0, vec![0x90, 0x3c, 0x64]), (1000, vec![0x90, 0x40, 0x50])]
+        );
+    }
+
+    #[test]
+    fn invalid_lines_are_rejected() {
+        assert!(parse_message("0 90 3c 64").is_ok());
+        assert!(parse_message("zero 90 3c 64").is_err());
+        assert!(parse_message("0 90 3g 64").is_err());
+        assert!(parse_message("0").is_err());
+        assert!(matches!(parse_message("0 90 c8 64"), Err(Error::Parse(_))));
+        assert!(matches!(parse_message("0 3c 64"), Err(Error::Parse(_))));
+    }
+
+    #[test]
+    fn port_key_strips_alsa_numbers() {
+        assert_eq!(
+            port_key("Piano:Piano MIDI 1 20:0"),
+            port_key("Piano:Piano MIDI 1 24:0")
+        );
+        assert_eq!(port_key("Piano:Piano MIDI 1 20:0"), "Piano:Piano MIDI 1");
+        assert_eq!(port_key("IAC Driver Bus 1"), "IAC Driver Bus 1");
+    }
+}
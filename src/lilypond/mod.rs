@@ -0,0 +1,298 @@
+use crate::{
+    chart::{self, Chart},
+    chord::{Chord, Fourth, Quality, Second, Seventh, Tension},
+    history::Moment,
+    note::{Accidental, Key, Mode, Note},
+};
+
+//"cis", "ees", "bes"
+fn pitch(note: &Note) -> String {
+    let suffix = match note.accidental {
+        Accidental::Flat => "es",
+        Accidental::Natural => "",
+        Accidental::Sharp => "is",
+    };
+    format!("{}{suffix}", note.name.to_string().to_lowercase())
+}
+
+//Absolute pitch, "c" being the C below middle C: "c'" "g,"
+fn absolute(note: &Note) -> String {
+    let octave = note.octave as i8 - 3;
+    let marks = match octave {
+        0.. => "'".repeat(octave as usize),
+        _ => ",".repeat(-octave as usize),
+    };
+    pitch(note) + &marks
+}
+
+//Chord mode modifiers: "m7", "maj9", "7.9-", "m7.5-", "sus4"
+fn modifiers(chord: &Chord) -> String {
+    let seventh = chord.extension.map(|extension| extension.seventh);
+    let degree = chord.extension.map_or(7, |extension| extension.degree);
+    let mut steps = vec![];
+
+    let base = match (chord.quality, seventh) {
+        (Quality::Power, _) => "1.5".to_string(),
+        (Quality::Major, None) => String::new(),
+        (Quality::Major, Some(Seventh::Major)) => format!("maj{degree}"),
+        (Quality::Major, Some(_)) => degree.to_string(),
+        (Quality::Minor, None) => "m".to_string(),
+        (Quality::Minor, Some(Seventh::Major)) if degree > 7 => format!("m{degree}.7+"),
+        (Quality::Minor, Some(Seventh::Major)) => "m7+".to_string(),
+        (Quality::Minor, Some(_)) => format!("m{degree}"),
+        (Quality::Diminished, None) => "dim".to_string(),
+        (Quality::Diminished, Some(Seventh::Diminished)) => format!("dim{degree}"),
+        (Quality::Diminished, Some(Seventh::Minor)) => format!("m{degree}.5-"),
+        (Quality::Diminished, Some(Seventh::Major)) if degree > 7 => format!("dim.7+.{degree}"),
+        (Quality::Diminished, Some(Seventh::Major)) => "dim.7+".to_string(),
+        (Quality::Augmented, None) => "aug".to_string(),
+        (Quality::Augmented, Some(Seventh::Major)) => format!("maj{degree}.5+"),
+        (Quality::Augmented, Some(_)) => format!("aug{degree}"),
+        (Quality::Suspended, _) => {
+            let seventh = match seventh {
+                Some(Seventh::Major) => format!("maj{degree}"),
+                Some(_) => degree.to_string(),
+                None => String::new(),
+            };
+            let (second, fourth) = chord
+                .suspension
+                .map_or((None, Some(Fourth::Perfect)), |suspension| {
+                    (suspension.second, suspension.fourth)
+                });
+            let suspension = match (second, fourth) {
+                (Some(_), None) => "sus2",
+                (Some(_), Some(_)) => "sus2.4",
+                (None, _) => "sus4",
+            };
+            if second == Some(Second::Minor) {
+                steps.push("2-");
+            }
+            if fourth == Some(Fourth::Augmented) {
+                steps.push("4+");
+            }
+            seventh + suspension
+        }
+    };
+
+    //A suspended minor chord keeps its third
+    if let (Quality::Minor, Some(suspension)) = (chord.quality, chord.suspension) {
+        if suspension.second.is_some() {
+            steps.push("2");
+        }
+        if suspension.fourth.is_some() {
+            steps.push("4");
+        }
+    }
+
+    for tension in &chord.tensions {
+        steps.push(match tension {
+            Tension::FlatNinth => "9-",
+            Tension::Ninth => "9",
+            Tension::SharpNinth => "9+",
+            Tension::Fourth => "11",
+            Tension::FlatFifth => "5-",
+            Tension::FlatThirteenth => "13-",
+            Tension::Thirteenth => "13",
+            Tension::MinorSeventh => "7",
+            Tension::MajorSeventh => "7+",
+        });
+    }
+
+    //Added notes on a triad need its fifth first, "9" alone would be a ninth chord
+    let base = match (base.as_str(), steps.is_empty()) {
+        (_, true) => base,
+        ("", false) => "5".to_string(),
+        ("m", false) => "m5".to_string(),
+        _ => base,
+    };
+    let mut modifiers = vec![base];
+    modifiers.extend(steps.iter().map(|step| step.to_string()));
+    modifiers.retain(|modifier| !modifier.is_empty());
+    modifiers.join(".")
+}
+
+//Root and the rest of the name ("d" ":m7/c"), the duration goes in between
+//Polychords are written with their lower chord
+fn chord_name(chord: &Chord) -> (String, String) {
+    let mut rest = String::new();
+    let modifiers = modifiers(chord);
+    if !modifiers.is_empty() {
+        rest += &format!(":{modifiers}");
+    }
+    if let Some(bass) = &chord.bass {
+        rest += &format!("/{}", pitch(bass));
+    }
+    (pitch(&chord.root), rest)
+}
+
+//Lowest played note, or the bass of the chord when the notes are unknown
+fn bass_note(moment: &Moment) -> Note {
+    match moment.notes.iter().min_by_key(|note| u8::from(*note)) {
+        Some(note) => *note,
+        None => moment.chord.bass.unwrap_or(moment.chord.root),
+    }
+}
+
+//"d4.:m7", the duration is only written when it changes as LilyPond keeps the last one
+fn push(
+    tokens: &mut Vec<String>,
+    last_duration: &mut String,
+    note: &str,
+    value: (u64, u64, bool),
+    rest: &str,
+) {
+    let (_, fraction, dotted) = value;
+    let duration = match dotted {
+        true => format!("{fraction}."),
+        false => fraction.to_string(),
+    };
+    let written = match *last_duration == duration {
+        true => "",
+        false => &duration,
+    };
+    tokens.push(format!("{note}{written}{rest}"));
+    *last_duration = duration;
+}
+
+fn key_name(key: &Key) -> String {
+    let mode = match key.mode {
+        Mode::Major => "major",
+        Mode::Minor => "minor",
+    };
+    format!("\\key {} \\{mode}", pitch(&key.tonic))
+}
+
+//Chord names above a bass staff holding the lowest played note of each chord
+pub fn render(chart: &Chart) -> String {
+    let mut chords = vec![];
+    let mut basses = vec![];
+    let mut chord_duration = String::new();
+    let mut bass_duration = String::new();
+
+    for pieces in chart.measures() {
+        let mut chord_tokens = vec![];
+        let mut bass_tokens = vec![];
+
+        for piece in &pieces {
+            let values = chart::note_values(piece.length);
+            for (i, value) in values.iter().enumerate() {
+                match piece.moment {
+                    //Chord mode has no ties, a held chord is repeated and only shown once
+                    Some(moment) => {
+                        let (root, rest) = chord_name(&moment.chord);
+                        push(&mut chord_tokens, &mut chord_duration, &root, *value, &rest);
+
+                        let tied = piece.held || i + 1 < values.len();
+                        let tie = if tied { "~" } else { "" };
+                        let bass = absolute(&bass_note(moment));
+                        push(&mut bass_tokens, &mut bass_duration, &bass, *value, tie);
+                    }
+                    None => {
+                        push(&mut chord_tokens, &mut chord_duration, "s", *value, "");
+                        push(&mut bass_tokens, &mut bass_duration, "r", *value, "");
+                    }
+                }
+            }
+        }
+
+        chords.push(chord_tokens.join(" "));
+        basses.push(bass_tokens.join(" "));
+    }
+
+    let key = chart
+        .key
+        .as_ref()
+        .map_or(String::new(), |key| key_name(key) + " ");
+    let time = format!("\\time {}", chart.time_signature);
+
+    format!(
+        "\\version \"2.24.0\"
+
+harmony = \\chordmode {{
+  {time}
+  {}
+}}
+
+bass = {{
+  \\clef bass {key}{time}
+  {}
+}}
+
+\\score {{
+  <<
+    \\new ChordNames \\with {{ chordChanges = ##t }} \\harmony
+    \\new Staff \\bass
+  >>
+  \\layout {{ }}
+}}",
+        chords.join(" |\n  "),
+        basses.join(" |\n  ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chord, config::Config, history::History, note::notes, tempo::Position};
+
+    fn chord(key_numbers: &[u8]) -> Chord {
+        let notes = notes(key_numbers);
+        chord::to_chord(&notes, &notes[0], &Config::default().scoring).remove(0)
+    }
+
+    fn name(key_numbers: &[u8]) -> String {
+        let (root, rest) = chord_name(&chord(key_numbers));
+        root + &rest
+    }
+
+    //Chords of the keys starting at bar and beat, at 120 bpm
+    fn history(chords: &[(&[u8], u64, u64)]) -> History {
+        let mut history = History::default();
+        for (key_numbers, bar, beat) in chords {
+            history.push(Moment {
+                stamp: ((bar - 1) * 4 + beat - 1) * 500_000,
+                position: Some(Position {
+                    bar: *bar,
+                    beat: *beat,
+                    bpm: 120,
+                }),
+                notes: notes(key_numbers),
+                chord: chord(key_numbers),
+            });
+        }
+        history
+    }
+
+    #[test]
+    fn chords_are_named_in_chord_mode() {
+        assert_eq!(name(&[60, 64, 67]), "c");
+        assert_eq!(name(&[61, 65, 68]), "cis");
+        assert_eq!(name(&[62, 65, 69, 72]), "d:m7");
+        assert_eq!(name(&[60, 64, 67, 71]), "c:maj7");
+        assert_eq!(name(&[60, 63, 66, 69]), "c:dim7");
+        assert_eq!(name(&[60, 63, 66, 70]), "c:m7.5-");
+        assert_eq!(name(&[60, 63, 66, 71]), "c:dim.7+");
+        assert_eq!(name(&[60, 64, 67, 70, 73]), "c:7.9-");
+        assert_eq!(name(&[60, 65, 67]), "c:sus4");
+        assert_eq!(name(&[53, 55, 59, 62]), "g:7/f");
+    }
+
+    #[test]
+    fn chart_is_rendered_with_the_bass_staff() {
+        let history = history(&[
+            (&[50, 65, 69, 72], 1, 1),
+            (&[43, 59, 62, 65], 2, 1),
+            (&[48, 64, 67, 71], 3, 1),
+        ]);
+        let mut config = Config::default();
+        config.set("key", "C").unwrap();
+        let lilypond = render(&Chart {
+            history: &history,
+            notation: config.notation(),
+            key: config.scoring.key,
+            time_signature: config.time_signature,
+        });
+        assert!(lilypond.contains("\\chordmode {\n  \\time 4/4\n  d1:m7 |\n  g:7 |\n  c:maj7\n}"));
+        assert!(lilypond.contains("\\clef bass \\key c \\major \\time 4/4\n  d1 |\n  g, |\n  c\n}"));
+    }
+}
@@ -1,31 +1,159 @@
+mod arpeggio;
+mod chart;
 mod chord;
+mod config;
+mod error;
+mod fretboard;
+mod history;
+mod input;
+mod lilypond;
 mod midi;
+mod musicxml;
+mod notation;
 mod note;
+mod output;
+mod quiz;
+mod scale;
+mod score;
+mod smf;
+mod tempo;
+mod transpose;
+mod voice_leading;
+mod voicing;
 
+use chart::{Chart, ChartFormat};
+use config::{Config, SharedConfig};
+use error::Error;
+use history::History;
 use midi::MidiKeyboard;
-use std::{error::Error, io::stdin};
+use std::{
+    env, fs,
+    io::stdin,
+    process,
+    sync::{Arc, Mutex},
+};
+
+fn exit_with(e: Error) -> ! {
+    eprintln!("[ERROR] {e}");
+    process::exit(e.exit_code())
+}
+
+//Prints the chart of the chord history, or writes it to a file
+fn write_chart(
+    config: &SharedConfig,
+    keyboard: &Mutex<MidiKeyboard>,
+    format: &str,
+    path: Option<&str>,
+) -> Result<(), Error> {
+    let config = config.lock().unwrap().clone();
+    let keyboard = keyboard.lock().unwrap();
+    let chart = Chart {
+        history: keyboard.history(),
+        notation: config.notation(),
+        key: config.scoring.key,
+        time_signature: config.time_signature,
+    };
+
+    let text = chart.render(format.parse::<ChartFormat>()?)?;
+    match path {
+        Some(path) => fs::write(path, text + "\n").map_err(error::file_error(path)),
+        None => {
+            println!("{text}");
+            Ok(())
+        }
+    }
+}
 
 fn main() {
-    let mut _midi_keyboard = MidiKeyboard::new();
+    let config = match Config::from_args(env::args().skip(1)) {
+        Ok(config) => Arc::new(Mutex::new(config)),
+        Err(e) => exit_with(e),
+    };
+
+    let keyboard = MidiKeyboard::new(config.clone());
+    let mut _inputs = match MidiKeyboard::connect(&keyboard) {
+        Ok(inputs) => inputs,
+        Err(e) => exit_with(e),
+    };
 
     loop {
         let mut user_cmd = String::new();
-        stdin()
-            .read_line(&mut user_cmd)
-            .expect("Failed to read stdin");
-        match user_cmd.trim_end() {
-            "exit" => break,
-            "reconnect" => {
-                _midi_keyboard = MidiKeyboard::new();
+        match stdin().read_line(&mut user_cmd) {
+            Ok(0) => break,
+            Ok(_) => (),
+            Err(e) => exit_with(Error::Io(e)),
+        }
+        let args: Vec<&str> = user_cmd.split_whitespace().collect();
+        match args.as_slice() {
+            ["exit"] => break,
+            ["reconnect"] => match MidiKeyboard::connect(&keyboard) {
+                Ok(inputs) => _inputs = inputs,
+                Err(e) => eprintln!("[ERROR] {e}"),
+            },
+            ["record", "stop"] => match keyboard.lock().unwrap().stop_recording() {
+                Ok(true) => (),
+                Ok(false) => println!("Not recording"),
+                Err(e) => eprintln!("[ERROR] {e}"),
+            },
+            ["record", path] => {
+                if let Err(e) = keyboard.lock().unwrap().start_recording(path) {
+                    eprintln!("[ERROR] {e}");
+                }
+            }
+            ["replay", path] | ["replay", path, "fast" | "realtime"] => {
+                let realtime = args.get(2) == Some(&"realtime");
+                match MidiKeyboard::replay(config.clone(), path, realtime) {
+                    Ok(history) => match keyboard.lock() {
+                        Ok(mut keyboard) => keyboard.set_history(history),
+                        Err(e) => eprintln!("[ERROR] {e}"),
+                    },
+                    Err(e) => eprintln!("[ERROR] {e}"),
+                }
+            }
+            ["history"] => {
+                let notation = config.lock().unwrap().notation();
+                println!("{}", keyboard.lock().unwrap().history().describe(&notation));
+            }
+            ["chart", format] | ["chart", format, _] => {
+                if let Err(e) = write_chart(&config, &keyboard, format, args.get(2).copied()) {
+                    eprintln!("[ERROR] {e}");
+                }
+            }
+            ["quiz", "skip"] => match keyboard.lock().unwrap().skip_question() {
+                Some(next) => println!("{next}"),
+                None => println!("No quiz running"),
+            },
+            ["quiz", "stop"] => match keyboard.lock().unwrap().stop_quiz() {
+                Some(report) => println!("{report}"),
+                None => println!("No quiz running"),
+            },
+            ["quiz"] | ["quiz", _] => match args.get(1).unwrap_or(&"easy").parse() {
+                Ok(level) => println!("{}", keyboard.lock().unwrap().start_quiz(level)),
+                Err(e) => eprintln!("[ERROR] {e}"),
+            },
+            ["history", "clear"] => keyboard.lock().unwrap().set_history(History::default()),
+            ["set", option, value] => {
+                if let Err(e) = config.lock().unwrap().set(option, value) {
+                    eprintln!("[ERROR] {e}");
+                }
             }
-            "help" => {
+            ["settings"] => println!("Settings:\n{}", config.lock().unwrap()),
+            ["help"] => {
                 println!(
                     "Commands:
                 \n\texit : exits the program
-                \n\treconnect : Reconnects the midi keyboard, you can use it to change inputs"
+                \n\treconnect : Selects the midi inputs again (unplugged keyboards are reconnected automatically)
+                \n\tset <option> <value> : Changes a setting (style, naming, accidentals, format, key, explain, onset_window, release_hold, velocity_threshold, accent_velocity, channels, channel_map, channel_mode, ports, split, arpeggio, arpeggio_window, tempo, time_signature, scales, chord_scales, voice_leading, fretboard, transpose, instrument, weight.<name>, config)
+                \n\tsettings : Shows the current settings
+                \n\trecord <file> : Records the received midi messages to a file, until \"record stop\"
+                \n\treplay <file> [fast|realtime] : Names the chords of a recorded session (as fast as possible by default), it becomes the chord history
+                \n\thistory : Shows the chords played with their bar and beat, \"history clear\" empties it
+                \n\tquiz [easy|medium|hard] : Asks for chords to play and keeps the score, \"quiz skip\" shows the answer, \"quiz stop\" ends it with the accuracy of each kind of chord
+                \n\tchart <chordpro|text|nashville|musicxml|lilypond> [file] : Writes the chord history as a chart (nashville numbers are relative to the key)"
                 )
             }
             _ => println!("Unknown command, type help to get available commands"),
         }
     }
+    keyboard.lock().unwrap().flush();
 }
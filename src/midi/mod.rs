@@ -1,11 +1,38 @@
-use crate::{chord, note::Note, Error};
-use midir::{Ignore, MidiInput, MidiInputConnection, MidiInputPort};
+use crate::{
+    arpeggio::{Arpeggio, ArpeggioMode},
+    chord::{self, Chord},
+    config::{Config, SharedConfig},
+    error::{self, Error},
+    history::{History, Moment},
+    input::{self, Connection, InputSource, MidiPort, Replay, VirtualPort},
+    note::Note,
+    output,
+    quiz::{Level, Quiz},
+    scale,
+    tempo::Tempo,
+    transpose,
+    voice_leading::{self, Analysis},
+    voicing,
+};
+use midir::{Ignore, MidiInput, MidiInputPort};
 use std::{
     fmt::{self, Display},
-    io::{self, Write},
-    ops::Deref,
+    fs::File,
+    io::{self, BufWriter, Write},
+    str::FromStr,
+    sync::{Arc, Mutex, Weak},
+    thread,
+    time::{Duration, Instant},
 };
 
+//How often pending chords are checked when no message comes in
+const TICK: Duration = Duration::from_millis(5);
+
+//How often the ports are checked for unplugged keyboards
+const WATCH: Duration = Duration::from_millis(500);
+
+const SUSTAIN_PEDAL: u8 = 64;
+
 enum Event {
     KeyOff,
     KeyOn,
@@ -17,148 +44,766 @@ enum Event {
     SystemMessage,
 }
 
-pub struct MidiKeyboard(u128);
+struct Message {
+    event: Event,
+    channel: u8,
+    data: [u8; 2],
+}
 
-impl Deref for MidiKeyboard {
-    type Target = u128;
+#[derive(Clone, Copy)]
+pub struct KeyState {
+    pub velocity: u8,
+    pub pressed_at: u64,
+    pub channel: u8,
+    //Key released but still sounding through the sustain pedal
+    pub pedal_held: bool,
+}
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+impl KeyState {
+    pub fn dynamic(&self) -> &'static str {
+        match self.velocity {
+            0..=15 => "ppp",
+            16..=31 => "pp",
+            32..=47 => "p",
+            48..=63 => "mp",
+            64..=79 => "mf",
+            80..=95 => "f",
+            96..=111 => "ff",
+            _ => "fff",
+        }
     }
 }
 
+pub struct PlayedNote {
+    pub note: Note,
+    pub flat_note: Option<Note>,
+    pub state: KeyState,
+}
+
+//Stamp (in microseconds) at which the keyboard state will be reported
+#[derive(Clone, Copy)]
+enum Pending {
+    Onset(u64),
+    Release(u64),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChannelMode {
+    Combined,
+    Separate,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ChannelRouting {
+    //Bitmap of the accepted channels (after mapping)
+    pub filter: u16,
+    pub map: [u8; 16],
+    pub mode: ChannelMode,
+}
+
+#[derive(Clone, PartialEq)]
+pub enum PortSelection {
+    Ask,
+    All,
+    //A port created for other applications to send to
+    Virtual,
+    //Messages read from a file
+    Replay(String),
+    //Port numbers or parts of port names
+    List(Vec<String>),
+}
+
+pub struct MidiKeyboard {
+    //Held keys of each channel
+    keys: [u128; 16],
+    key_states: [[Option<KeyState>; 128]; 16],
+    sustain_pedal: [bool; 16],
+    config: SharedConfig,
+    pending: Option<Pending>,
+    reported_keys: [u128; 16],
+    //Last message stamp and when it was received, to follow the stamps between messages
+    clock: Option<(u64, Instant)>,
+    started: Instant,
+    recorder: Option<BufWriter<File>>,
+    arpeggio: Option<Arpeggio>,
+    tempo: Tempo,
+    history: History,
+    //Stamp of the last change of the held keys
+    changed_at: u64,
+    quiz: Option<Quiz>,
+    //Last notes played and their chord, the voices are led from them
+    voicing: Option<(Vec<Note>, Option<Chord>)>,
+    //Notes of the last report, holding only some of them is a release rather than a new chord
+    sounding: Vec<Note>,
+}
+
+//A port followed by name, without connection while it is unplugged
+struct WatchedPort {
+    name: String,
+    connection: Option<Connection>,
+}
+
+//Connections of a keyboard, they are closed once dropped
+pub struct Inputs {
+    ports: Arc<Mutex<Vec<WatchedPort>>>,
+    others: Vec<Connection>,
+}
+
 impl MidiKeyboard {
-    pub fn new() -> Result<MidiInputConnection<MidiKeyboard>, Box<dyn Error>> {
-        let mut midi_in = MidiInput::new("Keyboard")?;
-        midi_in.ignore(Ignore::None);
+    //The keyboard is shared by its input connections
+    pub fn new(config: SharedConfig) -> Arc<Mutex<MidiKeyboard>> {
+        let new = Self::offline(config);
+        Self::spawn_ticker(Arc::downgrade(&new));
+        new
+    }
 
-        let midi_port = &Self::select_input_port_until_valid(&midi_in);
+    //Without ticker, pending chords are only reported by the stamps of the next messages so a
+    //recording always gives the same chords
+    pub fn offline(config: SharedConfig) -> Arc<Mutex<MidiKeyboard>> {
+        Arc::new(Mutex::new(MidiKeyboard {
+            keys: [0; 16],
+            key_states: [[None; 128]; 16],
+            sustain_pedal: [false; 16],
+            config,
+            pending: None,
+            reported_keys: [0; 16],
+            clock: None,
+            started: Instant::now(),
+            recorder: None,
+            arpeggio: None,
+            tempo: Tempo::default(),
+            history: History::default(),
+            changed_at: 0,
+            quiz: None,
+            voicing: None,
+            sounding: vec![],
+        }))
+    }
 
-        let new: MidiKeyboard = MidiKeyboard(0);
+    //Connects to the ports selected in the config
+    pub fn connect(keyboard: &Arc<Mutex<MidiKeyboard>>) -> Result<Inputs, Error> {
+        let config = keyboard.lock().unwrap().config.clone();
+        let selection = config.lock().unwrap().ports.clone();
 
-        Ok(midi_in.connect(&midi_port, "midir-in", Self::midi_callback, new)?)
+        let mut inputs = Inputs {
+            ports: Arc::new(Mutex::new(vec![])),
+            others: vec![],
+        };
+        match selection {
+            PortSelection::Virtual => {
+                let source = VirtualPort::new("midi2chord");
+                println!("Listening to {}", source.name());
+                inputs
+                    .others
+                    .push(Self::listen(keyboard, Box::new(source))?);
+            }
+            PortSelection::Replay(path) => {
+                let source = Replay::from_file(&path)?.realtime(true);
+                println!("Listening to {}", source.name());
+                inputs
+                    .others
+                    .push(Self::listen(keyboard, Box::new(source))?);
+            }
+            selection => {
+                let mut midi_in = MidiInput::new("Keyboard")?;
+                midi_in.ignore(Ignore::None);
+
+                let mut ports = inputs.ports.lock().unwrap();
+                for port_name in Self::select_input_ports_until_valid(&midi_in, &selection)? {
+                    println!("Listening to {port_name}");
+                    let connection = Self::listen(keyboard, Box::new(MidiPort::new(&port_name)))?;
+                    ports.push(WatchedPort {
+                        name: port_name,
+                        connection: Some(connection),
+                    });
+                }
+            }
+        }
+
+        Self::spawn_watcher(Arc::downgrade(keyboard), Arc::downgrade(&inputs.ports));
+        Ok(inputs)
     }
 
-    pub fn select_input_port(midi_in: &MidiInput) -> Result<MidiInputPort, Box<dyn Error>> {
-        let ports = midi_in.ports();
+    //Unplugged ports are reported and connected again by name once they come back,
+    //stops when the inputs are dropped
+    fn spawn_watcher(keyboard: Weak<Mutex<MidiKeyboard>>, ports: Weak<Mutex<Vec<WatchedPort>>>) {
+        thread::spawn(move || {
+            let Ok(midi_in) = MidiInput::new("Watcher") else {
+                return;
+            };
 
-        if ports.len() == 0 {
-            panic!("No midi port available")
+            loop {
+                thread::sleep(WATCH);
+
+                let (Some(keyboard), Some(ports)) = (keyboard.upgrade(), ports.upgrade()) else {
+                    break;
+                };
+                let available: Vec<String> = midi_in
+                    .ports()
+                    .iter()
+                    .filter_map(|port| midi_in.port_name(port).ok())
+                    .collect();
+
+                Self::watch(&keyboard, &mut ports.lock().unwrap(), &available, |name| {
+                    Self::listen(&keyboard, Box::new(MidiPort::new(name))).ok()
+                });
+            }
+        });
+    }
+
+    //Follows the ports in the available ones, an unplugged port releases the keys and a port
+    //plugged back (maybe with other ALSA numbers) is connected again
+    fn watch(
+        keyboard: &Mutex<MidiKeyboard>,
+        ports: &mut [WatchedPort],
+        available: &[String],
+        connect: impl Fn(&str) -> Option<Connection>,
+    ) {
+        for port in ports.iter_mut() {
+            let plugged = available
+                .iter()
+                .any(|name| input::port_key(name) == input::port_key(&port.name));
+            match &port.connection {
+                Some(_) if !plugged => {
+                    port.connection = None;
+                    keyboard.lock().unwrap().release_all();
+                    println!("[INFO] Port \"{}\" disconnected, waiting for it", port.name);
+                }
+                None if plugged => {
+                    if let Some(connection) = connect(&port.name) {
+                        port.connection = Some(connection);
+                        println!("[INFO] Port \"{}\" reconnected", port.name);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    //Replays a file on a keyboard of its own, the pending chord is reported at the end
+    pub fn replay(config: SharedConfig, path: &str, realtime: bool) -> Result<History, Error> {
+        let source = Replay::from_file(path)?.realtime(realtime);
+        let keyboard = match realtime {
+            true => Self::new(config),
+            false => Self::offline(config),
+        };
+        Self::listen(&keyboard, Box::new(source))?.join()?;
+
+        //A panic while naming the chords leaves the keyboard poisoned
+        let mut keyboard = keyboard
+            .lock()
+            .map_err(|_| Error::Connection(format!("Replay of \"{path}\" failed")))?;
+        keyboard.flush();
+        Ok(keyboard.history.clone())
+    }
+
+    //Reports the pending chord, at the end of a replay or when quitting
+    pub fn flush(&mut self) {
+        self.flush_due(u64::MAX);
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn set_history(&mut self, history: History) {
+        self.history = history;
+    }
+
+    //Every message received is written with its stamp until the recording is stopped
+    pub fn start_recording(&mut self, path: &str) -> Result<(), Error> {
+        let mut file = BufWriter::new(File::create(path).map_err(error::file_error(path))?);
+        writeln!(
+            file,
+            "# midi2chord session, stamp (microseconds) then bytes"
+        )
+        .map_err(error::file_error(path))?;
+        self.recorder = Some(file);
+        Ok(())
+    }
+
+    //Played chords are checked against the questions instead of being reported
+    pub fn start_quiz(&mut self, level: Level) -> String {
+        let config = self.config.lock().unwrap().clone();
+        self.quiz.insert(Quiz::new(level)).ask(&config)
+    }
+
+    pub fn skip_question(&mut self) -> Option<String> {
+        let config = self.config.lock().unwrap().clone();
+        self.quiz.as_mut().map(|quiz| quiz.skip(&config))
+    }
+
+    //Returns the score, or None when no quiz was running
+    pub fn stop_quiz(&mut self) -> Option<String> {
+        self.quiz.take().map(|quiz| quiz.report())
+    }
+
+    //Returns false when nothing was recorded
+    pub fn stop_recording(&mut self) -> Result<bool, Error> {
+        match self.recorder.take() {
+            Some(mut file) => file.flush().map(|_| true).map_err(Error::Io),
+            None => Ok(false),
         }
-        if ports.len() == 1 {
-            println!("Only one port available, selecting the only option");
-            println!("Selected port 0 - {}", midi_in.port_name(&ports[0])?);
-            return Ok(ports[0].clone());
+    }
+
+    //Messages of the source go through the midi callback until the connection is dropped
+    pub fn listen(
+        keyboard: &Arc<Mutex<MidiKeyboard>>,
+        source: Box<dyn InputSource>,
+    ) -> Result<Connection, Error> {
+        let offset = match source.is_live() {
+            true => keyboard.lock().unwrap().started.elapsed().as_micros() as u64,
+            false => 0,
+        };
+
+        let keyboard = keyboard.clone();
+        source.connect(Box::new(move |stamp, message| {
+            Self::midi_callback(stamp + offset, message, &keyboard)
+        }))
+    }
+
+    //Reports pending chords once their window is over, stops when the connections are dropped
+    fn spawn_ticker(keyboard: Weak<Mutex<MidiKeyboard>>) {
+        thread::spawn(move || loop {
+            thread::sleep(TICK);
+
+            let Some(keyboard) = keyboard.upgrade() else {
+                break;
+            };
+            let mut keyboard = keyboard.lock().unwrap();
+            if let Some((stamp, received)) = keyboard.clock {
+                let now = stamp + received.elapsed().as_micros() as u64;
+                keyboard.flush_due(now);
+            }
+        });
+    }
+
+    fn port_matches(midi_in: &MidiInput, ports: &[MidiInputPort], item: &str) -> Vec<usize> {
+        if let Ok(number) = item.parse::<usize>() {
+            return vec![number];
         }
+        (0..ports.len())
+            .filter(|i| {
+                midi_in
+                    .port_name(&ports[*i])
+                    .is_ok_and(|name| name.to_lowercase().contains(&item.to_lowercase()))
+            })
+            .collect()
+    }
 
-        println!("Please select midi input port: ");
+    //Returns the names of the selected ports
+    pub fn select_input_ports(
+        midi_in: &MidiInput,
+        selection: &PortSelection,
+    ) -> Result<Vec<String>, Error> {
+        let ports = midi_in.ports();
 
-        //Enumerate midi ports
-        for (i, port) in ports.iter().enumerate() {
-            let port_name = midi_in.port_name(port)?;
-            println!("  {i}) {port_name}");
+        if ports.is_empty() {
+            return Err(Error::NoPorts);
         }
-        io::stdout().flush()?;
 
-        //Reading user input
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+        let items: Vec<String> = match selection {
+            PortSelection::All | PortSelection::Virtual | PortSelection::Replay(_) => {
+                (0..ports.len()).map(|i| i.to_string()).collect()
+            }
+            PortSelection::List(items) => items.clone(),
+            PortSelection::Ask if ports.len() == 1 => {
+                println!("Only one port available, selecting the only option");
+                vec!["0".to_string()]
+            }
+            PortSelection::Ask => {
+                println!(
+                    "Please select midi input port (several can be given as \"0,2\" or \"all\"): "
+                );
+
+                //Enumerate midi ports
+                for (i, port) in ports.iter().enumerate() {
+                    let port_name = midi_in.port_name(port)?;
+                    println!("  {i}) {port_name}");
+                }
+                io::stdout().flush()?;
+
+                //Reading user input
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+
+                match input.trim().parse::<PortSelection>()? {
+                    PortSelection::All => (0..ports.len()).map(|i| i.to_string()).collect(),
+                    PortSelection::List(items) => items,
+                    _ => return Err(Error::InvalidSelection("No port selected".to_string())),
+                }
+            }
+        };
 
-        //Validating user input
-        let selection_number = input.trim().parse::<usize>()?;
-        if selection_number >= ports.len() {
-            return Err(format!(
-                "Selected a port out of range (0..{})",
-                ports.len() - 1
-            ))?;
+        //Validating the selection
+        let mut selected = vec![];
+        for item in items {
+            let matches = Self::port_matches(midi_in, &ports, &item);
+            if matches.is_empty() {
+                return Err(Error::InvalidSelection(format!(
+                    "No port matching \"{item}\""
+                )));
+            }
+            for selection_number in matches {
+                if selection_number >= ports.len() {
+                    return Err(Error::InvalidSelection(format!(
+                        "Selected a port out of range (0..{})",
+                        ports.len() - 1
+                    )));
+                }
+                if !selected.contains(&selection_number) {
+                    selected.push(selection_number);
+                }
+            }
         }
 
-        let port = ports.get(selection_number).unwrap();
-        println!(
-            "Selected port {selection_number}: {}",
-            midi_in.port_name(port)?
-        );
+        let mut port_names = vec![];
+        for selection_number in selected {
+            let port_name = midi_in.port_name(&ports[selection_number])?;
+            println!("Selected port {selection_number}: {port_name}");
+            port_names.push(port_name);
+        }
 
-        return Ok(ports.get(selection_number).unwrap().clone());
+        Ok(port_names)
     }
 
-    pub fn select_input_port_until_valid(midi_in: &MidiInput) -> MidiInputPort {
+    //Only a wrong selection is asked again, other errors are returned
+    pub fn select_input_ports_until_valid(
+        midi_in: &MidiInput,
+        selection: &PortSelection,
+    ) -> Result<Vec<String>, Error> {
+        let mut selection = selection.clone();
         loop {
-            match Self::select_input_port(midi_in) {
-                Ok(port) => return port,
-                Err(e) => eprintln!("[ERROR] {e}"),
+            match Self::select_input_ports(midi_in, &selection) {
+                Ok(port_names) => return Ok(port_names),
+                Err(e @ (Error::InvalidSelection(_) | Error::Parse(_))) => eprintln!("[ERROR] {e}"),
+                Err(e) => return Err(e),
             }
+            //A wrong configured selection is asked again
+            selection = PortSelection::Ask;
         }
     }
 
-    fn midi_callback(_stamp: u64, message: &[u8], keyboard: &mut MidiKeyboard) {
-        let (event, key_number) = Self::decode_message(message);
+    fn midi_callback(stamp: u64, message: &[u8], keyboard: &Mutex<MidiKeyboard>) {
+        let mut keyboard = keyboard.lock().unwrap();
+        if let Some(file) = &mut keyboard.recorder {
+            if let Err(e) = writeln!(file, "{}", input::format_message(stamp, message)) {
+                eprintln!("[ERROR] Recording stopped: {e}");
+                keyboard.recorder = None;
+            }
+        }
+        //Key numbers and velocities are 7 bits, a malformed message is ignored
+        if !input::is_valid_message(message) {
+            return;
+        }
+        let Message {
+            event,
+            channel,
+            data,
+        } = Self::decode_message(message);
+
+        keyboard.clock = Some((stamp, Instant::now()));
+        keyboard.flush_due(stamp);
+
+        //Channel messages are mapped and filtered, system messages keep their type in place of the channel
+        if let Event::SystemMessage = event {
+            keyboard.tempo.on_system_message(stamp, channel, data);
+            return;
+        }
+        let routing = keyboard.config.lock().unwrap().channels.clone();
+        let channel = routing.map[channel as usize];
+        if routing.filter & (0b1 << channel) == 0 {
+            return;
+        }
 
         match event {
-            Event::KeyOff => keyboard.on_key_released(key_number), //on_key_released(message[1]),
-            Event::KeyOn => keyboard.on_key_pressed(key_number),   //on_key_pressed(message[1]),
+            Event::KeyOff => keyboard.on_key_released(stamp, data[0], channel),
+            //A key on with a velocity of 0 is a key off
+            Event::KeyOn if data[1] == 0 => keyboard.on_key_released(stamp, data[0], channel),
+            Event::KeyOn => keyboard.on_key_pressed(stamp, data[0], data[1], channel),
             Event::PolyphonicKeyPressure => (), //println!("[INFO] Unhandeled message (Polyphonic Key Pressure)"),
+            Event::ControlChange if data[0] == SUSTAIN_PEDAL => {
+                keyboard.on_sustain_pedal(stamp, data[1] >= 64, channel)
+            }
             Event::ControlChange => (), //println!("[INFO] Unhandeled message (Control Change)"),
             Event::ProgramChange => (), //println!("[INFO] Unhandeled message (Program Change)"),
             Event::ChannelPressure => (), //println!("[INFO] Unhandeled message (Channel Pressure)"),
             Event::PitchBendChange => (), //println!("[INFO] Unhandeled message (Pitch Bend Change)"),
-            Event::SystemMessage => (),   //println!("[INFO] Unhandeled message (System message)"),
+            Event::SystemMessage => (),
+        }
+
+        //Without onset window nor release hold, the state is reported right away
+        keyboard.flush_due(stamp);
+    }
+
+    //Keys pressed within the onset window of the first one are reported as one chord
+    fn on_key_pressed(&mut self, stamp: u64, key_number: u8, velocity: u8, channel: u8) {
+        let channel_index = channel as usize;
+        let state = KeyState {
+            velocity,
+            pressed_at: stamp,
+            channel,
+            pedal_held: false,
+        };
+        self.keys[channel_index] |= 1u128 << key_number;
+        self.key_states[channel_index][key_number as usize] = Some(state);
+
+        let (mode, window) = self.arpeggio_settings();
+        if mode != ArpeggioMode::Off {
+            if self
+                .arpeggio
+                .as_ref()
+                .is_some_and(|group| group.closes(mode, window, stamp, key_number))
+            {
+                self.report_arpeggio();
+            }
+            if self.arpeggio.is_none() {
+                self.tempo.on_onset(stamp);
+            }
+            self.arpeggio
+                .get_or_insert_with(|| Arpeggio::new(stamp))
+                .add(stamp, key_number, state);
+            return;
         }
+
+        let onset_window = self.config.lock().unwrap().onset_window * 1000;
+        self.pending = match self.pending {
+            Some(Pending::Onset(deadline)) => Some(Pending::Onset(deadline)),
+            _ => {
+                self.changed_at = stamp;
+                self.tempo.on_onset(stamp);
+                Some(Pending::Onset(stamp + onset_window))
+            }
+        };
     }
 
-    fn on_key_pressed(&mut self, key_number: u8) {
-        self.0 |= (0b1 as u128) << key_number;
+    //Released keys are only reported once no other key moved during the release hold
+    fn on_key_released(&mut self, stamp: u64, key_number: u8, channel: u8) {
+        let channel_index = channel as usize;
+        if self.sustain_pedal[channel_index] {
+            if let Some(state) = &mut self.key_states[channel_index][key_number as usize] {
+                state.pedal_held = true;
+            }
+            return;
+        }
 
-        println!("{}", self);
+        self.keys[channel_index] &= !(1u128 << key_number);
+        self.key_states[channel_index][key_number as usize] = None;
+        self.schedule_release(stamp);
+    }
 
-        let note_opts = self.to_notes();
-        let mut notes = vec![];
-        for (note, _) in note_opts {
-            notes.push(note);
+    //Lifting the pedal releases every key only held by it
+    fn on_sustain_pedal(&mut self, stamp: u64, pressed: bool, channel: u8) {
+        let channel_index = channel as usize;
+        self.sustain_pedal[channel_index] = pressed;
+        if pressed {
+            return;
         }
 
-        let chords = chord::to_chord(notes);
+        for key_number in 0..128 {
+            let key_state = &mut self.key_states[channel_index][key_number];
+            if key_state.is_some_and(|state| state.pedal_held) {
+                *key_state = None;
+                self.keys[channel_index] &= !(1u128 << key_number);
+                self.schedule_release(stamp);
+            }
+        }
+    }
 
-        if chords.len() > 0 {
-            print!("Chords: ");
-            for chord in chords {
-                print!("\n\t{} [{}]", chord.0, chord.1);
+    fn schedule_release(&mut self, stamp: u64) {
+        let release_hold = self.config.lock().unwrap().release_hold * 1000;
+        self.pending = match self.pending {
+            Some(Pending::Onset(deadline)) => Some(Pending::Onset(deadline)),
+            Some(Pending::Release(deadline)) => {
+                Some(Pending::Release(deadline.max(stamp + release_hold)))
             }
-            println!();
-        } else {
-            println!("No chord found");
+            None => {
+                self.changed_at = stamp;
+                Some(Pending::Release(stamp + release_hold))
+            }
+        };
+    }
+
+    //Keys of an unplugged keyboard would never be released
+    fn release_all(&mut self) {
+        self.keys = [0; 16];
+        self.key_states = [[None; 128]; 16];
+        self.sustain_pedal = [false; 16];
+
+        let now = self.clock.map_or(0, |(stamp, received)| {
+            stamp + received.elapsed().as_micros() as u64
+        });
+        self.schedule_release(now);
+    }
+
+    //Arpeggio mode and window in microseconds
+    fn arpeggio_settings(&self) -> (ArpeggioMode, u64) {
+        let config = self.config.lock().unwrap();
+        (config.arpeggio, config.arpeggio_window * 1000)
+    }
+
+    fn report_arpeggio(&mut self) {
+        if let Some(group) = self.arpeggio.take() {
+            let config = self.config.lock().unwrap().clone();
+            self.changed_at = group.started();
+            let notes = group.to_notes(config.transposition());
+            self.print_state(&config, &notes, None);
         }
     }
 
-    fn on_key_released(&mut self, key_number: u8) {
-        self.0 &= !((0b1 as u128) << key_number);
+    fn flush_due(&mut self, now: u64) {
+        //Broken chords are reported on their own, the held keys are not
+        let (mode, window) = self.arpeggio_settings();
+        if mode != ArpeggioMode::Off || self.arpeggio.is_some() {
+            self.pending = None;
+            self.reported_keys = self.keys;
+            if self.arpeggio.as_ref().is_some_and(|group| {
+                mode == ArpeggioMode::Off || group.deadline(mode, window) <= now
+            }) {
+                self.report_arpeggio();
+            }
+            return;
+        }
 
-        println!("{}", self);
+        let deadline = match self.pending {
+            Some(Pending::Onset(deadline)) | Some(Pending::Release(deadline)) => deadline,
+            None => return,
+        };
+        if deadline > now {
+            return;
+        }
 
-        let note_opts = self.to_notes();
-        let mut notes = vec![];
-        for (note, _) in note_opts {
-            notes.push(note);
+        self.pending = None;
+        if self.keys == self.reported_keys {
+            return;
         }
 
-        if notes.len() > 0 {
-            let chords = chord::to_chord(notes);
-            if chords.len() > 0 {
-                print!("Chords: ");
-                for chord in chords {
-                    print!("\n\t{} [{}]", chord.0, chord.1);
+        let config = self.config.lock().unwrap().clone();
+        match config.channels.mode {
+            ChannelMode::Combined => {
+                let notes = self.to_notes();
+                self.print_state(&config, &notes, None)
+            }
+            ChannelMode::Separate => {
+                for channel in 0..16 {
+                    if self.keys[channel] != self.reported_keys[channel] {
+                        let notes = self.channel_notes(0b1 << channel);
+                        self.print_state(&config, &notes, Some(channel as u8));
+                    }
                 }
-                println!();
-            } else {
-                println!("No chord found");
             }
         }
+        self.reported_keys = self.keys;
     }
 
-    fn decode_message(message: &[u8]) -> (Event, u8) {
-        let (event_index, key_number) = (message[0], message[1]);
+    fn print_state(&mut self, config: &Config, played: &[PlayedNote], channel: Option<u8>) {
+        //Ghost notes are shown but left out of the analysis
+        let analysed: Vec<&PlayedNote> = played
+            .iter()
+            .filter(|played| played.state.velocity >= config.velocity_threshold)
+            .collect();
+        let notes: Vec<Note> = analysed.iter().map(|played| played.note).collect();
+        let accented: Vec<Note> = analysed
+            .iter()
+            .filter(|played| played.state.velocity >= config.accent_velocity)
+            .map(|played| played.note)
+            .collect();
+
+        //Notes are sorted by key number, the bass is the lowest key
+        let mut chords = match notes.first() {
+            Some(bass) => {
+                chord::to_chord_split(&notes, bass, config.split.as_ref(), &config.scoring)
+            }
+            None => vec![],
+        };
+        voicing::weight_accents(&mut chords, &accented, &config.scoring);
+
+        if let Some(split) = &config.split {
+            let left_hand: Vec<Note> = notes
+                .iter()
+                .filter(|note| u8::from(*note) < u8::from(split))
+                .copied()
+                .collect();
+            voicing::weight_split(&mut chords, &left_hand, &config.scoring);
+        }
 
-        let event = match event_index & 0b1111_0000 {
+        let position = self
+            .tempo
+            .position(self.changed_at, config.tempo, config.time_signature);
+        if position.is_some() {
+            let tempo = &self.tempo;
+            self.history
+                .locate(|stamp| tempo.position(stamp, config.tempo, config.time_signature));
+        }
+        if let Some(chord) = chords.first() {
+            self.history.push(Moment {
+                stamp: self.changed_at,
+                position,
+                notes: notes.clone(),
+                chord: chord.clone(),
+            });
+        }
+
+        let scales = match config.scales.shows_scales(&notes) {
+            true => scale::matching(&notes),
+            false => vec![],
+        };
+
+        let voice_leading = self.lead_voices(config, &notes, chords.first());
+
+        match &mut self.quiz {
+            Some(quiz) => {
+                if let Some(verdict) = quiz.answer(config, &notes, &chords) {
+                    println!("{verdict}");
+                }
+            }
+            None => output::print_report(
+                config,
+                played,
+                &chords,
+                &scales,
+                voice_leading.as_ref(),
+                channel,
+                position,
+            ),
+        }
+    }
+
+    //Releasing some of the keys is not a new chord, the voices go on from the full one. Once
+    //every key is released, any chord played is a new one
+    fn lead_voices(
+        &mut self,
+        config: &Config,
+        notes: &[Note],
+        chord: Option<&Chord>,
+    ) -> Option<Analysis> {
+        let released = notes.iter().all(|note| self.sounding.contains(note));
+        self.sounding = notes.to_vec();
+        if released {
+            return None;
+        }
+
+        let previous = self.voicing.replace((notes.to_vec(), chord.cloned()));
+        match (config.voice_leading, previous) {
+            (true, Some((from, from_chord))) => Some(voice_leading::analyse(
+                &from,
+                from_chord.as_ref(),
+                notes,
+                chord,
+            )),
+            _ => None,
+        }
+    }
+
+    fn decode_message(message: &[u8]) -> Message {
+        //System messages like the MIDI clock are a single byte
+        let status = message[0];
+        let data = [
+            message.get(1).copied().unwrap_or(0),
+            message.get(2).copied().unwrap_or(0),
+        ];
+
+        let event = match status & 0b1111_0000 {
             0b1000_0000 => Event::KeyOff,
             0b1001_0000 => Event::KeyOn,
             0b1010_0000 => Event::PolyphonicKeyPressure,
@@ -173,21 +818,39 @@ impl MidiKeyboard {
             }
         };
 
-        (event, key_number)
+        Message {
+            event,
+            channel: status & 0b0000_1111,
+            data,
+        }
+    }
+
+    //Keys held on every channel, a key held on several channels is only given once,
+    //notes are moved by the transposition and instrument settings
+    pub fn to_notes(&self) -> Vec<PlayedNote> {
+        self.channel_notes(u16::MAX)
     }
 
-    pub fn to_notes(&self) -> Vec<(Note, Option<Note>)> {
+    pub fn channel_notes(&self, channels: u16) -> Vec<PlayedNote> {
+        let transposition = self.config.lock().unwrap().transposition();
         let mut result = vec![];
 
         let mut bit_mask: u128 = 0b1;
 
         for i in 1..128 {
-            bit_mask = bit_mask << 1;
-            if (**self & bit_mask) != 0 {
-                match Note::from(i) {
-                    Some(n) => result.push((n.0, n.1)),
-                    None => (),
-                }
+            bit_mask <<= 1;
+            let Some(channel) =
+                (0..16).find(|c| channels & (0b1 << c) != 0 && self.keys[*c] & bit_mask != 0)
+            else {
+                continue;
+            };
+            let note = transpose::transpose(i, transposition).and_then(Note::from);
+            if let (Some(n), Some(state)) = (note, self.key_states[channel][i as usize]) {
+                result.push(PlayedNote {
+                    note: n.0,
+                    flat_note: n.1,
+                    state,
+                })
             }
         }
 
@@ -195,21 +858,478 @@ impl MidiKeyboard {
     }
 }
 
-impl Display for MidiKeyboard {
-    fn fmt(&self, _f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let notes = self.to_notes();
-        if notes.len() == 0 {
-            print!("-");
+//Once every input is gone (end of a replay, reconnection), what is still pending is reported
+impl FromStr for ChannelMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "combined" => Ok(ChannelMode::Combined),
+            "separate" => Ok(ChannelMode::Separate),
+            _ => Err(format!(
+                "Unknown channel mode \"{s}\" (available: combined, separate)"
+            )),
+        }
+    }
+}
+
+impl Default for ChannelRouting {
+    fn default() -> Self {
+        ChannelRouting {
+            filter: u16::MAX,
+            map: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+            mode: ChannelMode::Combined,
+        }
+    }
+}
+
+fn parse_channel(value: &str) -> Result<u8, String> {
+    match value.trim().parse::<u8>() {
+        Ok(channel @ 1..=16) => Ok(channel - 1),
+        _ => Err(format!("Invalid channel \"{value}\", expected 1 to 16")),
+    }
+}
+
+impl ChannelRouting {
+    //"all" or channels like "1,2,10" or "1-4"
+    pub fn set_filter(&mut self, value: &str) -> Result<(), Error> {
+        if value == "all" {
+            self.filter = u16::MAX;
             return Ok(());
         }
 
-        for note in notes {
-            print!("{}", note.0);
-            match note.1 {
-                Some(flat_note) => print!("/{flat_note} "),
-                None => print!(" "),
+        let mut filter = 0;
+        for item in value.split(',') {
+            let (first, last) = match item.split_once('-') {
+                Some((first, last)) => (parse_channel(first)?, parse_channel(last)?),
+                None => (parse_channel(item)?, parse_channel(item)?),
+            };
+            for channel in first..=last {
+                filter |= 0b1 << channel;
+            }
+        }
+        self.filter = filter;
+        Ok(())
+    }
+
+    //"none" or mappings like "2=1,3=1"
+    pub fn set_map(&mut self, value: &str) -> Result<(), Error> {
+        let mut map = ChannelRouting::default().map;
+        if value != "none" {
+            for item in value.split(',') {
+                let Some((from, to)) = item.split_once('=') else {
+                    return Err(Error::Parse(format!(
+                        "Invalid channel mapping \"{item}\", expected from=to"
+                    )));
+                };
+                map[parse_channel(from)? as usize] = parse_channel(to)?;
             }
         }
+        self.map = map;
         Ok(())
     }
 }
+
+impl Display for ChannelRouting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let channels: Vec<String> = (0..16)
+            .filter(|channel| self.filter & (0b1 << channel) != 0)
+            .map(|channel| (channel + 1).to_string())
+            .collect();
+        let map: Vec<String> = (0..16)
+            .filter(|channel| self.map[*channel] as usize != *channel)
+            .map(|channel| format!("{}={}", channel + 1, self.map[channel] + 1))
+            .collect();
+        let mode = match self.mode {
+            ChannelMode::Combined => "combined",
+            ChannelMode::Separate => "separate",
+        };
+
+        match self.filter {
+            u16::MAX => writeln!(f, "\tchannels : all")?,
+            _ => writeln!(f, "\tchannels : {}", channels.join(","))?,
+        }
+        match map.is_empty() {
+            true => writeln!(f, "\tchannel_map : none")?,
+            false => writeln!(f, "\tchannel_map : {}", map.join(","))?,
+        }
+        write!(f, "\tchannel_mode : {mode}")
+    }
+}
+
+impl FromStr for PortSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ask" => Ok(PortSelection::Ask),
+            "all" => Ok(PortSelection::All),
+            "virtual" => Ok(PortSelection::Virtual),
+            "" => Err("No port selected".to_string()),
+            _ if s.starts_with("replay:") => {
+                Ok(PortSelection::Replay(s["replay:".len()..].to_string()))
+            }
+            list => Ok(PortSelection::List(
+                list.split(',')
+                    .map(|item| item.trim().to_string())
+                    .collect(),
+            )),
+        }
+    }
+}
+
+impl Display for PortSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PortSelection::Ask => write!(f, "ask"),
+            PortSelection::All => write!(f, "all"),
+            PortSelection::Virtual => write!(f, "virtual"),
+            PortSelection::Replay(path) => write!(f, "replay:{path}"),
+            PortSelection::List(items) => write!(f, "{}", items.join(",")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    //Messages pressing the keys together at a stamp (in milliseconds)
+    fn press(millis: u64, keys: &[u8]) -> Vec<(u64, Vec<u8>)> {
+        keys.iter()
+            .map(|key| (millis * 1000, vec![0x90, *key, 80]))
+            .collect()
+    }
+
+    fn press_softly(millis: u64, keys: &[u8], velocity: u8) -> Vec<(u64, Vec<u8>)> {
+        keys.iter()
+            .map(|key| (millis * 1000, vec![0x90, *key, velocity]))
+            .collect()
+    }
+
+    //Key on messages of another channel (from 0)
+    fn press_on(millis: u64, keys: &[u8], channel: u8) -> Vec<(u64, Vec<u8>)> {
+        keys.iter()
+            .map(|key| (millis * 1000, vec![0x90 | channel, *key, 80]))
+            .collect()
+    }
+
+    fn release(millis: u64, keys: &[u8]) -> Vec<(u64, Vec<u8>)> {
+        keys.iter()
+            .map(|key| (millis * 1000, vec![0x80, *key, 0]))
+            .collect()
+    }
+
+    //Sends the messages through a replay as a keyboard would receive them
+    fn play(config: Config, messages: Vec<(u64, Vec<u8>)>) -> Arc<Mutex<MidiKeyboard>> {
+        let keyboard = MidiKeyboard::offline(Arc::new(Mutex::new(config)));
+        let source = Replay::new("test", messages);
+        MidiKeyboard::listen(&keyboard, Box::new(source))
+            .unwrap()
+            .join()
+            .unwrap();
+        keyboard.lock().unwrap().flush();
+        keyboard
+    }
+
+    //Chords of the history, as the settings write them
+    fn chords(config: Config, messages: Vec<(u64, Vec<u8>)>) -> Vec<String> {
+        let notation = config.notation();
+        let keyboard = play(config, messages);
+        let keyboard = keyboard.lock().unwrap();
+        keyboard
+            .history()
+            .moments()
+            .iter()
+            .map(|moment| notation.chord(&moment.chord))
+            .collect()
+    }
+
+    fn keyboard(config: Config) -> Arc<Mutex<MidiKeyboard>> {
+        MidiKeyboard::offline(Arc::new(Mutex::new(config)))
+    }
+
+    fn send(keyboard: &Arc<Mutex<MidiKeyboard>>, messages: Vec<(u64, Vec<u8>)>) {
+        for (stamp, message) in messages {
+            MidiKeyboard::midi_callback(stamp, &message, keyboard);
+        }
+    }
+
+    fn progression() -> Vec<(u64, Vec<u8>)> {
+        [
+            press(0, &[60, 64, 67]),
+            release(500, &[60, 64, 67]),
+            press(1000, &[55, 59, 62, 65]),
+            release(1500, &[55, 59, 62, 65]),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn replayed_chords_are_named() {
+        assert_eq!(chords(Config::default(), progression()), ["C", "G7"]);
+    }
+
+    #[test]
+    fn keys_within_the_onset_window_are_one_chord() {
+        let messages = [press(0, &[60]), press(20, &[64]), press(40, &[67])].concat();
+        assert_eq!(chords(Config::default(), messages), ["C"]);
+    }
+
+    #[test]
+    fn releasing_every_key_adds_no_chord() {
+        let messages = [press(0, &[60, 64, 67]), release(500, &[60, 64, 67])].concat();
+        assert_eq!(chords(Config::default(), messages), ["C"]);
+    }
+
+    #[test]
+    fn replay_is_deterministic() {
+        let first = chords(Config::default(), progression());
+        for _ in 0..5 {
+            assert_eq!(chords(Config::default(), progression()), first);
+        }
+    }
+
+    #[test]
+    fn keys_after_the_onset_window_are_another_chord() {
+        let mut config = Config::default();
+        config.set("onset_window", "10").unwrap();
+        let messages = [press(0, &[60, 64, 67]), press(100, &[70])].concat();
+        assert_eq!(chords(config, messages), ["C", "C7"]);
+    }
+
+    #[test]
+    fn key_changed_within_the_release_hold_is_one_chord() {
+        let changed = [
+            press(0, &[60, 64, 67]),
+            release(500, &[67]),
+            press(520, &[69]),
+        ]
+        .concat();
+        assert_eq!(chords(Config::default(), changed), ["C", "Amin/C"]);
+        let released = [
+            press(0, &[60, 64, 67]),
+            release(500, &[67]),
+            press(700, &[69]),
+        ]
+        .concat();
+        assert_eq!(chords(Config::default(), released), ["C", "Amin/C"]);
+    }
+
+    #[test]
+    fn ghost_notes_are_left_out() {
+        let messages = [press(0, &[60, 64, 67]), press_softly(0, &[70], 10)].concat();
+        assert_eq!(chords(Config::default(), messages.clone()), ["C7"]);
+        let mut config = Config::default();
+        config.set("velocity_threshold", "20").unwrap();
+        assert_eq!(chords(config, messages), ["C"]);
+    }
+
+    #[test]
+    fn accented_tensions_change_the_chord() {
+        let messages = [press(0, &[60, 64, 67]), press(0, &[69])].concat();
+        assert_eq!(chords(Config::default(), messages), ["C(13)"]);
+        let accented = [press(0, &[60, 64, 67]), press_softly(0, &[69], 120)].concat();
+        assert_eq!(chords(Config::default(), accented), ["Amin7/C"]);
+    }
+
+    #[test]
+    fn velocity_and_press_time_are_kept_per_key() {
+        let keyboard = keyboard(Config::default());
+        send(
+            &keyboard,
+            [press(0, &[60]), press_softly(20, &[64], 10)].concat(),
+        );
+        let keyboard = keyboard.lock().unwrap();
+        let state = keyboard.key_states[0][64].unwrap();
+        assert_eq!((state.velocity, state.pressed_at), (10, 20_000));
+        assert_eq!(keyboard.key_states[0][60].unwrap().velocity, 80);
+    }
+
+    #[test]
+    fn malformed_messages_are_ignored() {
+        let keyboard = keyboard(Config::default());
+        send(&keyboard, vec![(0, vec![0x90, 200, 80]), (10, vec![])]);
+        let keyboard = keyboard.lock().unwrap();
+        assert_eq!(keyboard.keys[0], 0);
+    }
+
+    #[test]
+    fn sustain_pedal_holds_the_released_keys() {
+        let messages = [
+            press(0, &[60, 64, 67]),
+            vec![(100_000, vec![0xB0, 64, 127])],
+            release(200, &[60, 64, 67]),
+            press(300, &[70]),
+            vec![(400_000, vec![0xB0, 64, 0])],
+        ]
+        .concat();
+        assert_eq!(chords(Config::default(), messages), ["C", "C7"]);
+    }
+
+    //C major on channel 1 and a G7 on channel 2, pressed together
+    fn two_channels() -> Vec<(u64, Vec<u8>)> {
+        [
+            press_on(0, &[60, 64, 67], 0),
+            press_on(0, &[43, 47, 50, 53], 1),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn channels_are_combined() {
+        assert_eq!(chords(Config::default(), two_channels()), ["Cmaj11/G"]);
+    }
+
+    #[test]
+    fn channels_are_named_separately() {
+        let mut config = Config::default();
+        config.set("channel_mode", "separate").unwrap();
+        assert_eq!(chords(config, two_channels()), ["C", "G7"]);
+    }
+
+    #[test]
+    fn channels_are_filtered_and_mapped() {
+        let mut config = Config::default();
+        config.set("channels", "2").unwrap();
+        assert_eq!(chords(config, two_channels()), ["G7"]);
+        let mut config = Config::default();
+        config.set("channel_map", "2=1").unwrap();
+        config.set("channels", "1").unwrap();
+        assert_eq!(chords(config, two_channels()), ["Cmaj11/G"]);
+    }
+
+    #[test]
+    fn recorded_session_replays_the_same_chords() {
+        let path =
+            std::env::temp_dir().join(format!("midi2chord-session-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let keyboard = MidiKeyboard::offline(Arc::new(Mutex::new(Config::default())));
+        keyboard.lock().unwrap().start_recording(path).unwrap();
+        MidiKeyboard::listen(&keyboard, Box::new(Replay::new("test", progression())))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert!(keyboard.lock().unwrap().stop_recording().unwrap());
+
+        let keyboard = MidiKeyboard::offline(Arc::new(Mutex::new(Config::default())));
+        let source = Replay::from_file(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        MidiKeyboard::listen(&keyboard, Box::new(source))
+            .unwrap()
+            .join()
+            .unwrap();
+        let mut keyboard = keyboard.lock().unwrap();
+        keyboard.flush();
+        let notation = Config::default().notation();
+        let chords: Vec<String> = keyboard
+            .history()
+            .moments()
+            .iter()
+            .map(|moment| notation.chord(&moment.chord))
+            .collect();
+        assert_eq!(chords, ["C", "G7"]);
+        assert!(!keyboard.stop_recording().unwrap());
+    }
+
+    #[test]
+    fn malformed_replay_file_is_an_error() {
+        let path =
+            std::env::temp_dir().join(format!("midi2chord-malformed-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "0 90 3c 64\n10 90 c8 64\n").unwrap();
+        let replay = MidiKeyboard::replay(Arc::new(Mutex::new(Config::default())), path, false);
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(replay, Err(Error::Parse(_))));
+    }
+
+    //Keys pressed one after the other, each released before the next one
+    fn broken(from: u64, keys: &[u8]) -> Vec<(u64, Vec<u8>)> {
+        keys.iter()
+            .enumerate()
+            .flat_map(|(i, key)| {
+                let millis = from + 100 * i as u64;
+                [press(millis, &[*key]), release(millis + 80, &[*key])].concat()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn broken_chord_is_named_per_window() {
+        let messages = [broken(0, &[48, 52, 55, 52]), broken(600, &[47, 50, 55, 50])].concat();
+        let mut config = Config::default();
+        config.set("arpeggio", "window").unwrap();
+        assert_eq!(chords(config, messages), ["C", "G/B"]);
+    }
+
+    #[test]
+    fn broken_chord_is_named_until_the_bass_moves() {
+        //Alberti bass without pause between the chords
+        let messages = [broken(0, &[48, 55, 52, 55]), broken(400, &[47, 55, 50, 55])].concat();
+        let mut config = Config::default();
+        config.set("arpeggio", "bass").unwrap();
+        assert_eq!(chords(config, messages.clone()), ["C", "G/B"]);
+        assert!(chords(Config::default(), messages).is_empty());
+    }
+
+    #[test]
+    fn chords_are_written_for_the_instrument() {
+        let mut config = Config::default();
+        config.set("instrument", "bb").unwrap();
+        assert_eq!(chords(config, progression()), ["D", "A7"]);
+
+        let mut config = Config::default();
+        config.set("instrument", "capo:3").unwrap();
+        config.set("transpose", "5").unwrap();
+        assert_eq!(chords(config, progression()), ["D", "A7"]);
+    }
+
+    //Connection of a replay with no message, it stands for an open port
+    fn connection() -> Option<Connection> {
+        let source = Box::new(Replay::new("test", vec![]));
+        source.connect(Box::new(|_, _| ())).ok()
+    }
+
+    fn watched(connection: Option<Connection>) -> Vec<WatchedPort> {
+        vec![WatchedPort {
+            name: "Piano:Piano MIDI 1 20:0".to_string(),
+            connection,
+        }]
+    }
+
+    #[test]
+    fn unplugged_port_releases_the_keys() {
+        let keyboard = keyboard(Config::default());
+        send(&keyboard, press(0, &[60, 64, 67]));
+        let mut ports = watched(connection());
+
+        MidiKeyboard::watch(&keyboard, &mut ports, &[], |_| panic!("Port is unplugged"));
+        assert!(ports[0].connection.is_none());
+        let keyboard = keyboard.lock().unwrap();
+        assert_eq!(keyboard.keys[0], 0);
+        assert!(keyboard.key_states[0][60].is_none());
+    }
+
+    #[test]
+    fn plugged_back_port_is_connected_again() {
+        let keyboard = keyboard(Config::default());
+        let mut ports = watched(None);
+        let connected = Mutex::new(vec![]);
+        let connect = |name: &str| {
+            connected.lock().unwrap().push(name.to_string());
+            connection()
+        };
+
+        MidiKeyboard::watch(&keyboard, &mut ports, &["Organ 20:0".to_string()], connect);
+        assert!(ports[0].connection.is_none());
+
+        //Plugged back with another client number
+        let available = ["Piano:Piano MIDI 1 24:0".to_string()];
+        MidiKeyboard::watch(&keyboard, &mut ports, &available, connect);
+        assert!(ports[0].connection.is_some());
+        MidiKeyboard::watch(&keyboard, &mut ports, &available, connect);
+        assert_eq!(*connected.lock().unwrap(), ["Piano:Piano MIDI 1 20:0"]);
+    }
+}
@@ -0,0 +1,379 @@
+use crate::{
+    chart::{self, Chart, Piece},
+    chord::{Chord, Fourth, Quality, Second, Seventh, Suspension, Tension},
+    note::{Accidental, Key, Mode, Note},
+};
+
+//Divisions of a quarter note, the scores are counted in sixteenths
+const DIVISIONS: u64 = 4;
+
+fn alter(accidental: Accidental) -> i8 {
+    match accidental {
+        Accidental::Flat => -1,
+        Accidental::Natural => 0,
+        Accidental::Sharp => 1,
+    }
+}
+
+//"<root-step>D</root-step><root-alter>-1</root-alter>"
+fn step(tag: &str, note: &Note) -> String {
+    let mut xml = format!("<{tag}-step>{}</{tag}-step>", note.name);
+    if note.accidental != Accidental::Natural {
+        xml += &format!("<{tag}-alter>{}</{tag}-alter>", alter(note.accidental));
+    }
+    xml
+}
+
+//Sharps (positive) or flats (negative) of the key signature
+fn fifths(key: &Key) -> i8 {
+    let major_tonic = match key.mode {
+        Mode::Major => key.tonic.pitch_class(),
+        Mode::Minor => (key.tonic.pitch_class() + 3) % 12,
+    };
+    match (major_tonic, key.tonic.accidental) {
+        (6, Accidental::Flat) => -6,
+        (1, Accidental::Sharp) => 7,
+        (pitch_class, _) => [0, -5, 2, -3, 4, -1, 6, 1, -4, 3, -2, 5][pitch_class as usize],
+    }
+}
+
+//MusicXML kind and the degrees added, altered or left out from it
+fn kind(chord: &Chord, notes: &[Note]) -> (&'static str, Vec<(u8, i8, &'static str)>) {
+    let mut degrees = vec![];
+    let seventh = chord.extension.map(|extension| extension.seventh);
+    let degree = chord.extension.map_or(7, |extension| extension.degree);
+
+    let kind = match (chord.quality, seventh) {
+        (Quality::Power, _) => "power",
+        (Quality::Major, None) => "major",
+        (Quality::Major, Some(Seventh::Major)) => match degree {
+            9 => "major-ninth",
+            11 => "major-11th",
+            13 => "major-13th",
+            _ => "major-seventh",
+        },
+        (Quality::Major, Some(_)) => match degree {
+            9 => "dominant-ninth",
+            11 => "dominant-11th",
+            13 => "dominant-13th",
+            _ => "dominant",
+        },
+        (Quality::Minor, None) => "minor",
+        (Quality::Minor, Some(Seventh::Major)) => "major-minor",
+        (Quality::Minor, Some(_)) => match degree {
+            9 => "minor-ninth",
+            11 => "minor-11th",
+            13 => "minor-13th",
+            _ => "minor-seventh",
+        },
+        (Quality::Diminished, None) => "diminished",
+        (Quality::Diminished, Some(Seventh::Diminished)) => "diminished-seventh",
+        (Quality::Diminished, Some(Seventh::Minor)) => "half-diminished",
+        //No kind has a major seventh over a diminished triad
+        (Quality::Diminished, Some(Seventh::Major)) => {
+            degrees.push((7, 0, "add"));
+            "diminished"
+        }
+        (Quality::Augmented, None) => "augmented",
+        (Quality::Augmented, Some(Seventh::Major)) => {
+            degrees.push((5, 1, "alter"));
+            "major-seventh"
+        }
+        (Quality::Augmented, Some(_)) => "augmented-seventh",
+        (Quality::Suspended, _) => {
+            let suspension = chord.suspension.unwrap_or(Suspension {
+                second: None,
+                fourth: Some(Fourth::Perfect),
+            });
+            if let Some(seventh) = seventh {
+                let alter = match seventh {
+                    Seventh::Major => 0,
+                    _ => -1,
+                };
+                degrees.push((7, alter, "add"));
+            }
+            match (suspension.second, suspension.fourth) {
+                (Some(Second::Major), None) => "suspended-second",
+                (Some(Second::Minor), None) => {
+                    degrees.push((2, -1, "alter"));
+                    "suspended-second"
+                }
+                (second, fourth) => {
+                    match second {
+                        Some(Second::Major) => degrees.push((2, 0, "add")),
+                        Some(Second::Minor) => degrees.push((2, -1, "add")),
+                        None => (),
+                    }
+                    if fourth == Some(Fourth::Augmented) {
+                        degrees.push((4, 1, "alter"));
+                    }
+                    "suspended-fourth"
+                }
+            }
+        }
+    };
+
+    //A suspended minor chord keeps its third
+    if chord.quality == Quality::Minor {
+        if let Some(suspension) = chord.suspension {
+            if suspension.second.is_some() {
+                degrees.push((2, 0, "add"));
+            }
+            if suspension.fourth.is_some() {
+                degrees.push((4, 0, "add"));
+            }
+        }
+    }
+
+    let natural_fifth = notes.iter().any(|note| chord.interval(note) == 7);
+    for tension in &chord.tensions {
+        degrees.push(match tension {
+            Tension::FlatNinth => (9, -1, "add"),
+            Tension::Ninth => (9, 0, "add"),
+            Tension::SharpNinth => (9, 1, "add"),
+            Tension::Fourth => (11, 0, "add"),
+            //Along with the natural fifth it is a ♯11
+            Tension::FlatFifth if natural_fifth => (11, 1, "add"),
+            Tension::FlatFifth => (5, -1, "alter"),
+            Tension::FlatThirteenth => (13, -1, "add"),
+            Tension::Thirteenth => (13, 0, "add"),
+            Tension::MinorSeventh => (7, -1, "add"),
+            Tension::MajorSeventh => (7, 0, "add"),
+        });
+    }
+
+    (kind, degrees)
+}
+
+//Polychords are written with their lower chord
+fn harmony(chord: &Chord, notes: &[Note]) -> String {
+    let (kind, degrees) = kind(chord, notes);
+
+    let mut xml = format!(
+        "<harmony><root>{}</root><kind>{kind}</kind>",
+        step("root", &chord.root)
+    );
+    if let Some(bass) = &chord.bass {
+        xml += &format!("<bass>{}</bass>", step("bass", bass));
+    }
+    for (value, alter, kind) in degrees {
+        xml += &format!(
+            "<degree><degree-value>{value}</degree-value><degree-alter>{alter}</degree-alter><degree-type>{kind}</degree-type></degree>"
+        );
+    }
+    xml + "</harmony>"
+}
+
+fn note_type(fraction: u64) -> &'static str {
+    match fraction {
+        1 => "whole",
+        2 => "half",
+        4 => "quarter",
+        8 => "eighth",
+        _ => "16th",
+    }
+}
+
+//Notes of a chord (or a rest when there are none) filling the piece, tied over its note values
+fn notes(piece: &Piece) -> String {
+    let notes = piece.moment.map_or(&[][..], |moment| &moment.notes[..]);
+    let values = chart::note_values(piece.length);
+    let mut xml = String::new();
+
+    for (i, (duration, fraction, dotted)) in values.iter().enumerate() {
+        let tie_stop = !piece.onset || i > 0;
+        let tie_start = piece.held || i + 1 < values.len();
+        let kind = note_type(*fraction);
+        let dot = if *dotted { "<dot/>" } else { "" };
+
+        if notes.is_empty() {
+            xml += &format!(
+                "<note><rest/><duration>{duration}</duration><type>{kind}</type>{dot}</note>"
+            );
+            continue;
+        }
+        for (j, note) in notes.iter().enumerate() {
+            let chord = if j > 0 { "<chord/>" } else { "" };
+            let mut pitch = format!("<step>{}</step>", note.name);
+            if note.accidental != Accidental::Natural {
+                pitch += &format!("<alter>{}</alter>", alter(note.accidental));
+            }
+            pitch += &format!("<octave>{}</octave>", note.octave);
+
+            let mut ties = String::new();
+            let mut tied = String::new();
+            if tie_stop {
+                ties += "<tie type=\"stop\"/>";
+                tied += "<tied type=\"stop\"/>";
+            }
+            if tie_start {
+                ties += "<tie type=\"start\"/>";
+                tied += "<tied type=\"start\"/>";
+            }
+            let notations = if tied.is_empty() {
+                String::new()
+            } else {
+                format!("<notations>{tied}</notations>")
+            };
+
+            xml += &format!(
+                "<note>{chord}<pitch>{pitch}</pitch><duration>{duration}</duration>{ties}<type>{kind}</type>{dot}{notations}</note>"
+            );
+        }
+    }
+    xml
+}
+
+//Score with one staff holding the played notes, quantized to the beat, and the chord symbols
+pub fn render(chart: &Chart) -> String {
+    let moments = chart.history.moments();
+    let average_key = moments
+        .iter()
+        .flat_map(|moment| moment.notes.iter().map(u8::from))
+        .map(|key| key as u32)
+        .sum::<u32>()
+        .checked_div(moments.iter().map(|moment| moment.notes.len() as u32).sum())
+        .unwrap_or(60);
+    let clef = match average_key {
+        0..=59 => "<clef><sign>F</sign><line>4</line></clef>",
+        _ => "<clef><sign>G</sign><line>2</line></clef>",
+    };
+    let key = match &chart.key {
+        Some(key) => {
+            let mode = match key.mode {
+                Mode::Major => "major",
+                Mode::Minor => "minor",
+            };
+            format!(
+                "<key><fifths>{}</fifths><mode>{mode}</mode></key>",
+                fifths(key)
+            )
+        }
+        None => "<key><fifths>0</fifths></key>".to_string(),
+    };
+
+    let mut measures = vec![];
+    for (i, pieces) in chart.measures().iter().enumerate() {
+        let mut xml = format!("<measure number=\"{}\">", i + 1);
+        if i == 0 {
+            xml += &format!(
+                "<attributes><divisions>{DIVISIONS}</divisions>{key}<time><beats>{}</beats><beat-type>{}</beat-type></time>{clef}</attributes>",
+                chart.time_signature.beats, chart.time_signature.unit
+            );
+        }
+        for piece in pieces {
+            if let (Some(moment), true) = (piece.moment, piece.onset) {
+                xml += &harmony(&moment.chord, &moment.notes);
+            }
+            xml += &notes(piece);
+        }
+        measures.push(xml + "</measure>");
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>
+<!DOCTYPE score-partwise PUBLIC \"-//Recordare//DTD MusicXML 4.0 Partwise//EN\" \"http://www.musicxml.org/dtds/partwise.dtd\">
+<score-partwise version=\"4.0\">
+<part-list><score-part id=\"P1\"><part-name>Keyboard</part-name></score-part></part-list>
+<part id=\"P1\">
+{}
+</part>
+</score-partwise>",
+        measures.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chord,
+        config::Config,
+        history::{History, Moment},
+        note::notes,
+        tempo::Position,
+    };
+
+    //Harmony of the chord built on the lowest key
+    fn harmony_of(key_numbers: &[u8]) -> String {
+        let notes = notes(key_numbers);
+        let config = Config::default();
+        let chord = chord::to_chord(&notes, &notes[0], &config.scoring)
+            .into_iter()
+            .find(|chord| chord.root == notes[0] && chord.upper.is_none())
+            .unwrap();
+        harmony(&chord, &notes)
+    }
+
+    #[test]
+    fn flat_fifth_with_the_fifth_is_a_sharp_eleventh() {
+        //C E G B♭ F♯
+        let xml = harmony_of(&[60, 64, 67, 70, 78]);
+        assert!(xml.contains("<degree-value>11</degree-value><degree-alter>1</degree-alter><degree-type>add</degree-type>"));
+        assert!(!xml.contains("<degree-value>5</degree-value>"));
+    }
+
+    #[test]
+    fn chords_are_written_as_harmony_kinds() {
+        assert_eq!(
+            harmony_of(&[62, 65, 69, 72]),
+            "<harmony><root><root-step>D</root-step></root><kind>minor-seventh</kind></harmony>"
+        );
+        assert_eq!(
+            harmony_of(&[61, 65, 68]),
+            "<harmony><root><root-step>C</root-step><root-alter>1</root-alter></root><kind>major</kind></harmony>"
+        );
+        assert!(harmony_of(&[43, 59, 62, 65]).contains("<kind>dominant</kind>"));
+    }
+
+    #[test]
+    fn diminished_sevenths_are_told_apart() {
+        assert!(harmony_of(&[60, 63, 66, 69]).contains("<kind>diminished-seventh</kind>"));
+        assert!(harmony_of(&[60, 63, 66, 70]).contains("<kind>half-diminished</kind>"));
+        //C E♭ G♭ B
+        let xml = harmony_of(&[60, 63, 66, 71]);
+        assert!(xml.contains("<kind>diminished</kind><degree><degree-value>7</degree-value><degree-alter>0</degree-alter><degree-type>add</degree-type></degree>"));
+    }
+
+    #[test]
+    fn inversion_is_written_with_the_bass() {
+        //G7 over F
+        let notes = notes(&[53, 55, 59, 62]);
+        let chords = chord::to_chord(&notes, &notes[0], &Config::default().scoring);
+        assert_eq!(
+            harmony(&chords[0], &notes),
+            "<harmony><root><root-step>G</root-step></root><kind>dominant</kind><bass><bass-step>F</bass-step></bass></harmony>"
+        );
+    }
+
+    #[test]
+    fn score_starts_with_the_key_and_time() {
+        let notes = notes(&[53, 60, 65, 69]);
+        let mut config = Config::default();
+        config.set("key", "F").unwrap();
+        config.set("time_signature", "3/4").unwrap();
+        let mut history = History::default();
+        history.push(Moment {
+            stamp: 0,
+            position: Some(Position {
+                bar: 1,
+                beat: 1,
+                bpm: 120,
+            }),
+            chord: chord::to_chord(&notes, &notes[0], &config.scoring).remove(0),
+            notes,
+        });
+        let xml = render(&Chart {
+            history: &history,
+            notation: config.notation(),
+            key: config.scoring.key,
+            time_signature: config.time_signature,
+        });
+        assert!(xml.contains("<measure number=\"1\"><attributes><divisions>4</divisions><key><fifths>-1</fifths><mode>major</mode></key><time><beats>3</beats><beat-type>4</beat-type></time>"));
+        assert!(xml.contains(
+            "<harmony><root><root-step>F</root-step></root><kind>major</kind></harmony>"
+        ));
+        //The chord lasts the bar
+        assert!(xml.contains("<duration>12</duration><type>half</type><dot/>"));
+    }
+}
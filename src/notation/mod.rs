@@ -0,0 +1,496 @@
+use crate::{
+    chord::{Chord, Fourth, Quality, Second, Seventh, Stacking, Suspension, Tension},
+    note::{Accidental, Key, Mode, Naming, Note},
+    scale::Scale,
+};
+use std::{fmt, str::FromStr};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Style {
+    Classic,
+    Jazz,
+    Berklee,
+    Pop,
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "classic" | "default" => Ok(Style::Classic),
+            "jazz" => Ok(Style::Jazz),
+            "berklee" | "classical" => Ok(Style::Berklee),
+            "pop" | "ascii" => Ok(Style::Pop),
+            _ => Err(format!(
+                "Unknown style \"{s}\" (available: classic, jazz, berklee, pop)"
+            )),
+        }
+    }
+}
+
+const SUPERSCRIPTS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+
+//Sharp and flat signs, the style ones or the same for every style
+#[derive(Clone, Copy, PartialEq)]
+pub enum Symbols {
+    Style,
+    Ascii,
+    Unicode,
+}
+
+//How chords and notes are written, the key gives the movable-do tonic
+#[derive(Clone, Copy)]
+pub struct Notation {
+    pub style: Style,
+    pub naming: Naming,
+    pub symbols: Symbols,
+    pub key: Option<Key>,
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Style::Classic => write!(f, "classic"),
+            Style::Jazz => write!(f, "jazz"),
+            Style::Berklee => write!(f, "berklee"),
+            Style::Pop => write!(f, "pop"),
+        }
+    }
+}
+
+impl FromStr for Symbols {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "style" => Ok(Symbols::Style),
+            "ascii" => Ok(Symbols::Ascii),
+            "unicode" => Ok(Symbols::Unicode),
+            _ => Err(format!(
+                "Unknown accidentals \"{s}\" (available: style, ascii, unicode)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Symbols {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Symbols::Style => write!(f, "style"),
+            Symbols::Ascii => write!(f, "ascii"),
+            Symbols::Unicode => write!(f, "unicode"),
+        }
+    }
+}
+
+impl Notation {
+    fn sharp(&self) -> &'static str {
+        match (self.symbols, self.style) {
+            (Symbols::Ascii, _) | (Symbols::Style, Style::Classic | Style::Pop) => "#",
+            (Symbols::Unicode, _) | (Symbols::Style, Style::Jazz | Style::Berklee) => "♯",
+        }
+    }
+
+    fn flat(&self) -> &'static str {
+        match (self.symbols, self.style) {
+            (Symbols::Ascii, _) | (Symbols::Style, Style::Pop) => "b",
+            _ => "♭",
+        }
+    }
+
+    pub fn accidental(&self, accidental: Accidental) -> &'static str {
+        match accidental {
+            Accidental::Sharp => self.sharp(),
+            Accidental::Flat => self.flat(),
+            Accidental::Natural => "",
+        }
+    }
+
+    //Note name without octave (chord roots, slash basses), movable-do falls back to fixed-do
+    //without key
+    pub fn pitch(&self, note: &Note) -> String {
+        match (self.naming, &self.key) {
+            (Naming::English, _) => format!("{}{}", note.name, self.accidental(note.accidental)),
+            (Naming::German, _) => note.german(),
+            (Naming::MovableDo, Some(key)) => note.movable_syllable(&key.tonic).to_string(),
+            (Naming::FixedDo, _) | (Naming::MovableDo, None) => {
+                format!(
+                    "{}{}",
+                    note.name.syllable(),
+                    self.accidental(note.accidental)
+                )
+            }
+        }
+    }
+
+    //Name of a key tonic, in fixed-do for movable-do as its tonic would always be Do
+    pub fn tonic(&self, key: &Key) -> String {
+        let naming = match self.naming {
+            Naming::MovableDo => Naming::FixedDo,
+            naming => naming,
+        };
+        Notation { naming, ..*self }.pitch(&key.tonic)
+    }
+
+    pub fn key(&self, key: &Key) -> String {
+        let mode = match key.mode {
+            Mode::Major => "major",
+            Mode::Minor => "minor",
+        };
+        format!("{} {mode}", self.tonic(key))
+    }
+
+    pub fn note(&self, note: &Note) -> String {
+        format!("{}{}", self.pitch(note), note.octave)
+    }
+
+    pub fn notes(&self, notes: &[(Note, Option<Note>)]) -> String {
+        if notes.is_empty() {
+            return "-".to_string();
+        }
+
+        let mut result = vec![];
+        for (note, flat_note) in notes {
+            match flat_note {
+                Some(flat_note) => {
+                    result.push(format!("{}/{}", self.note(note), self.note(flat_note)))
+                }
+                None => result.push(self.note(note)),
+            }
+        }
+        result.join(" ")
+    }
+
+    //"D Dorian ♭2", the accidentals of the scale name follow the style
+    pub fn scale(&self, scale: &Scale) -> String {
+        let name = scale
+            .name
+            .replace('♭', self.flat())
+            .replace('♯', self.sharp());
+        format!("{} {name}", self.pitch(&scale.root))
+    }
+
+    //Scale degree above a chord root, as tension numbers ("♭9", "♯11", "13")
+    pub fn degree(&self, interval: u8) -> String {
+        match interval % 12 {
+            0 => "1".to_string(),
+            1 => format!("{}9", self.flat()),
+            2 => "9".to_string(),
+            3 => format!("{}9", self.sharp()),
+            4 => "3".to_string(),
+            5 => "11".to_string(),
+            6 => format!("{}11", self.sharp()),
+            7 => "5".to_string(),
+            8 => format!("{}13", self.flat()),
+            9 => "13".to_string(),
+            10 => format!("{}7", self.flat()),
+            _ => "7".to_string(),
+        }
+    }
+
+    fn minor(&self) -> &'static str {
+        match self.style {
+            Style::Classic => "min",
+            Style::Jazz | Style::Pop => "m",
+            Style::Berklee => "-",
+        }
+    }
+
+    fn diminished(&self) -> &'static str {
+        match self.style {
+            Style::Classic | Style::Pop => "dim",
+            Style::Jazz => "°",
+            Style::Berklee => "o",
+        }
+    }
+
+    fn augmented(&self) -> &'static str {
+        match self.style {
+            Style::Classic | Style::Pop => "aug",
+            Style::Jazz | Style::Berklee => "+",
+        }
+    }
+
+    fn major_seventh(&self) -> &'static str {
+        match self.style {
+            Style::Jazz => "Δ",
+            _ => "maj",
+        }
+    }
+
+    fn suspension(&self, suspension: &Suspension) -> String {
+        let second = match suspension.second {
+            Some(Second::Major) => Some("2".to_string()),
+            Some(Second::Minor) => Some(format!("{}2", self.flat())),
+            None => None,
+        };
+        let fourth = match suspension.fourth {
+            Some(Fourth::Perfect) => Some("4".to_string()),
+            Some(Fourth::Augmented) => Some(format!("{}4", self.sharp())),
+            None => None,
+        };
+
+        match (second, fourth) {
+            (Some(second), Some(fourth)) => format!("sus({second}/{fourth})"),
+            (Some(interval), None) | (None, Some(interval)) => format!("sus{interval}"),
+            (None, None) => String::new(),
+        }
+    }
+
+    fn tension(&self, tension: Tension) -> String {
+        match tension {
+            Tension::FlatNinth => format!("{}9", self.flat()),
+            Tension::Ninth => "9".to_string(),
+            Tension::SharpNinth => format!("{}9", self.sharp()),
+            Tension::Fourth => "4".to_string(),
+            Tension::FlatFifth => format!("{}5", self.flat()),
+            Tension::FlatThirteenth => format!("{}13", self.flat()),
+            Tension::Thirteenth => "13".to_string(),
+            Tension::MinorSeventh => "7".to_string(),
+            Tension::MajorSeventh => format!("{}7", self.major_seventh()),
+        }
+    }
+
+    //Quality and extension, the part of the name between the root and the added tensions
+    fn body(&self, chord: &Chord) -> String {
+        let quality = match chord.quality {
+            Quality::Power => "5".to_string(),
+            Quality::Major => String::new(),
+            Quality::Minor => match &chord.suspension {
+                Some(sus) => format!("{}({})", self.minor(), self.suspension(sus)),
+                None => self.minor().to_string(),
+            },
+            Quality::Diminished => self.diminished().to_string(),
+            Quality::Augmented => self.augmented().to_string(),
+            Quality::Suspended => match &chord.suspension {
+                Some(sus) => self.suspension(sus),
+                None => String::new(),
+            },
+        };
+
+        let Some(extension) = chord.extension else {
+            return quality;
+        };
+        let degree = extension.degree;
+
+        match (chord.quality, extension.seventh) {
+            (Quality::Diminished, Seventh::Minor) => match self.style {
+                Style::Classic => format!("min{degree}({}5)", self.flat()),
+                Style::Jazz => format!("ø{degree}"),
+                Style::Berklee => format!("-{degree}({}5)", self.flat()),
+                Style::Pop => format!("m{degree}{}5", self.flat()),
+            },
+            (Quality::Minor, Seventh::Major) => match self.style {
+                Style::Jazz => format!("{quality}Δ{degree}"),
+                Style::Classic | Style::Berklee | Style::Pop => {
+                    format!("{quality}(maj{degree})")
+                }
+            },
+            (_, Seventh::Major) => format!("{quality}{}{degree}", self.major_seventh()),
+            (_, Seventh::Minor) | (_, Seventh::Diminished) => format!("{quality}{degree}"),
+        }
+    }
+
+    pub fn chord(&self, chord: &Chord) -> String {
+        self.spell(chord, &|note| self.pitch(note))
+    }
+
+    //Nashville number of a note relative to the key tonic ("4", "♭7")
+    pub fn number(&self, note: &Note, key: &Key) -> String {
+        let interval = (note.pitch_class() + 12 - key.tonic.pitch_class()) % 12;
+        match interval {
+            0 => "1".to_string(),
+            1 => format!("{}2", self.flat()),
+            2 => "2".to_string(),
+            3 => format!("{}3", self.flat()),
+            4 => "3".to_string(),
+            5 => "4".to_string(),
+            6 => format!("{}5", self.flat()),
+            7 => "5".to_string(),
+            8 => format!("{}6", self.flat()),
+            9 => "6".to_string(),
+            10 => format!("{}7", self.flat()),
+            _ => "7".to_string(),
+        }
+    }
+
+    //Chord name with numbers in place of the roots and basses ("2m7", "5⁷", "5/7")
+    pub fn nashville(&self, chord: &Chord, key: &Key) -> String {
+        self.spell(chord, &|note| self.number(note, key))
+    }
+
+    //Leading number of a chord body as superscript, in parentheses with ascii signs
+    fn raised(&self, body: &str) -> String {
+        let end = body
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(body.len());
+        let (number, rest) = body.split_at(end);
+        match (self.symbols, self.style) {
+            (Symbols::Ascii, _) | (Symbols::Style, Style::Pop) => format!("({number}){rest}"),
+            _ => {
+                let number: String = number
+                    .chars()
+                    .map(|c| SUPERSCRIPTS[c as usize - '0' as usize])
+                    .collect();
+                format!("{number}{rest}")
+            }
+        }
+    }
+
+    fn spell(&self, chord: &Chord, pitch: &dyn Fn(&Note) -> String) -> String {
+        let root = pitch(&chord.root);
+        let mut body = self.body(chord);
+        //A number root is kept apart from the chord degree ("5⁷", not "57")
+        if root.ends_with(|c: char| c.is_ascii_digit())
+            && body.starts_with(|c: char| c.is_ascii_digit())
+        {
+            body = self.raised(&body);
+        }
+        let mut name = format!("{root}{body}");
+
+        match self.style {
+            Style::Berklee if !chord.tensions.is_empty() => {
+                let tensions: Vec<String> =
+                    chord.tensions.iter().map(|t| self.tension(*t)).collect();
+                name += &format!("({})", tensions.join(", "));
+            }
+            _ => {
+                for tension in &chord.tensions {
+                    let natural = matches!(
+                        tension,
+                        Tension::Ninth | Tension::Fourth | Tension::Thirteenth
+                    );
+                    if self.style == Style::Pop && chord.extension.is_none() && natural {
+                        name += &format!("add{}", self.tension(*tension));
+                    } else {
+                        name += &format!("({})", self.tension(*tension));
+                    }
+                }
+            }
+        }
+
+        if let Some(bass) = &chord.bass {
+            name += &format!("/{}", pitch(bass));
+        }
+
+        match &chord.upper {
+            Some(upper) => match upper.stacking {
+                Stacking::Polychord if chord.is_triad() && upper.chord.is_triad() => {
+                    format!("{}|{name}", self.spell(&upper.chord, pitch))
+                }
+                Stacking::Polychord => format!("{}/{name}", self.spell(&upper.chord, pitch)),
+                Stacking::UpperStructure => {
+                    format!("{name} UST {}", self.numeral(chord, &upper.chord))
+                }
+            },
+            None => name,
+        }
+    }
+
+    //Roman numeral of the upper triad from the lower root, lowercase for minor ("II", "♭vi")
+    fn numeral(&self, lower: &Chord, upper: &Chord) -> String {
+        let numeral = match lower.interval(&upper.root) {
+            0 => "I".to_string(),
+            1 => format!("{}II", self.flat()),
+            2 => "II".to_string(),
+            3 => format!("{}III", self.flat()),
+            4 => "III".to_string(),
+            5 => "IV".to_string(),
+            6 => format!("{}V", self.flat()),
+            7 => "V".to_string(),
+            8 => format!("{}VI", self.flat()),
+            9 => "VI".to_string(),
+            10 => format!("{}VII", self.flat()),
+            _ => "VII".to_string(),
+        };
+        match upper.quality {
+            Quality::Minor => numeral.to_lowercase(),
+            _ => numeral,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chord, config::Config, note::notes};
+
+    fn nashville(key_numbers: &[u8], symbols: Symbols) -> String {
+        let notes = notes(key_numbers);
+        let mut config = Config::default();
+        config.set("key", "C").unwrap();
+        config.symbols = symbols;
+        let chords = chord::to_chord(&notes, &notes[0], &config.scoring);
+        let key = config.scoring.key.unwrap();
+        config.notation().nashville(&chords[0], &key)
+    }
+
+    #[test]
+    fn nashville_degree_is_apart_from_the_number() {
+        assert_eq!(nashville(&[55, 59, 62, 65], Symbols::Style), "5⁷");
+        assert_eq!(nashville(&[55, 59, 62, 65], Symbols::Ascii), "5(7)");
+        assert_eq!(nashville(&[53, 55, 59, 62], Symbols::Style), "5⁷/4");
+        assert_eq!(nashville(&[62, 65, 69, 72], Symbols::Style), "2min7");
+        assert_eq!(nashville(&[60, 64, 67], Symbols::Style), "1");
+    }
+
+    //Dmin7, Cmaj7, C half-diminished, Cdim7, Caug, G7(♭9) and C minor-major as the settings write them
+    fn styled(style: &str, accidentals: &str) -> Vec<String> {
+        let mut config = Config::default();
+        config.set("style", style).unwrap();
+        config.set("accidentals", accidentals).unwrap();
+        let chords: [&[u8]; 7] = [
+            &[62, 65, 69, 72],
+            &[60, 64, 67, 71],
+            &[60, 63, 66, 70],
+            &[60, 63, 66, 69],
+            &[60, 64, 68],
+            &[55, 59, 62, 65, 68],
+            &[60, 63, 67, 71],
+        ];
+        chords
+            .iter()
+            .map(|key_numbers| {
+                let notes = notes(key_numbers);
+                let chords = chord::to_chord(&notes, &notes[0], &config.scoring);
+                config.notation().chord(&chords[0])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chords_are_written_in_the_style() {
+        assert_eq!(
+            styled("classic", "style"),
+            [
+                "Dmin7",
+                "Cmaj7",
+                "Cmin7(♭5)",
+                "Cdim7",
+                "Caug",
+                "G7(♭9)",
+                "Cmin(maj7)"
+            ]
+        );
+        assert_eq!(
+            styled("jazz", "style"),
+            ["Dm7", "CΔ7", "Cø7", "C°7", "C+", "G7(♭9)", "CmΔ7"]
+        );
+        assert_eq!(
+            styled("berklee", "style"),
+            ["D-7", "Cmaj7", "C-7(♭5)", "Co7", "C+", "G7(♭9)", "C-(maj7)"]
+        );
+        assert_eq!(
+            styled("pop", "style"),
+            ["Dm7", "Cmaj7", "Cm7b5", "Cdim7", "Caug", "G7(b9)", "Cm(maj7)"]
+        );
+    }
+
+    #[test]
+    fn accidentals_override_the_style() {
+        assert_eq!(styled("classic", "ascii")[2], "Cmin7(b5)");
+        assert_eq!(styled("jazz", "ascii")[5], "G7(b9)");
+        assert_eq!(styled("pop", "unicode")[2], "Cm7♭5");
+    }
+}
@@ -1,4 +1,36 @@
-use std::fmt;
+use crate::error::Error;
+use std::{fmt, str::FromStr};
+
+//Solfège syllables of the natural notes from A
+const SYLLABLES: [&str; 7] = ["La", "Si", "Do", "Ré", "Mi", "Fa", "Sol"];
+
+//Movable-do syllables by semitones from the tonic, raised then lowered for the chromatic ones
+const MOVABLE_SYLLABLES: [(&str, &str); 12] = [
+    ("Do", "Do"),
+    ("Di", "Ra"),
+    ("Re", "Re"),
+    ("Ri", "Me"),
+    ("Mi", "Mi"),
+    ("Fa", "Fa"),
+    ("Fi", "Se"),
+    ("Sol", "Sol"),
+    ("Si", "Le"),
+    ("La", "La"),
+    ("Li", "Te"),
+    ("Ti", "Ti"),
+];
+
+//How note names are written and read
+#[derive(Clone, Copy, PartialEq)]
+pub enum Naming {
+    English,
+    //H for B, B for B♭, Fis and Es for the sharps and flats
+    German,
+    //Do Ré Mi, Do is always C
+    FixedDo,
+    //Do is the tonic of the key, C without key
+    MovableDo,
+}
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum Name {
@@ -25,12 +57,30 @@ pub struct Note {
     pub octave: u8,
 }
 
+#[derive(PartialEq, Clone, Copy)]
+pub enum Mode {
+    Major,
+    Minor,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub struct Key {
+    pub tonic: Note,
+    pub mode: Mode,
+}
+
 impl Name {
     fn discriminant(&self) -> u8 {
         unsafe { *<*const _>::from(self).cast::<u8>() }
     }
 }
 
+impl Name {
+    pub fn syllable(&self) -> &'static str {
+        SYLLABLES[self.discriminant() as usize]
+    }
+}
+
 impl Accidental {
     fn discriminant(&self) -> i8 {
         unsafe { *<*const _>::from(self).cast::<i8>() }
@@ -39,7 +89,7 @@ impl Accidental {
 
 impl fmt::Display for Name {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let name = ('A' as u8 + self.discriminant()) as char;
+        let name = (b'A' + self.discriminant()) as char;
         write!(f, "{name}")
     }
 }
@@ -63,9 +113,9 @@ impl fmt::Display for Note {
 impl Note {
     pub fn new(name: Name, accidental: Accidental, octave: u8) -> Note {
         Note {
-            name: name,
-            accidental: accidental,
-            octave: octave,
+            name,
+            accidental,
+            octave,
         }
     }
 
@@ -115,7 +165,7 @@ impl Note {
                 Note {
                     name: name_map_sharp[number as usize],
                     accidental: Accidental::Natural,
-                    octave: octave,
+                    octave,
                 },
                 None,
             ))
@@ -124,12 +174,12 @@ impl Note {
                 Note {
                     name: name_map_sharp[number as usize],
                     accidental: Accidental::Sharp,
-                    octave: octave,
+                    octave,
                 },
                 Some(Note {
                     name: name_map_flat[number as usize],
                     accidental: Accidental::Flat,
-                    octave: octave,
+                    octave,
                 }),
             ))
         }
@@ -142,6 +192,54 @@ impl Note {
     pub fn interval_midi_number(number_a: u8, number_b: u8) -> i8 {
         (number_b - number_a) as i8
     }
+
+    pub fn pitch_class(&self) -> u8 {
+        u8::from(self) % 12
+    }
+
+    //"H", "B" (B♭), "Fis", "Es", "As", "Ces"
+    pub fn german(&self) -> String {
+        match (self.name, self.accidental) {
+            (Name::B, Accidental::Natural) => "H".to_string(),
+            (Name::B, Accidental::Flat) => "B".to_string(),
+            (Name::B, Accidental::Sharp) => "His".to_string(),
+            (name, Accidental::Natural) => name.to_string(),
+            (name, Accidental::Sharp) => format!("{name}is"),
+            (Name::A | Name::E, Accidental::Flat) => format!("{}s", self.name),
+            (name, Accidental::Flat) => format!("{name}es"),
+        }
+    }
+
+    //Syllable from the tonic ("Sol", "Te"), a sharp note is raised and a flat one lowered
+    pub fn movable_syllable(&self, tonic: &Note) -> &'static str {
+        let interval = (self.pitch_class() + 12 - tonic.pitch_class()) % 12;
+        let (raised, lowered) = MOVABLE_SYLLABLES[interval as usize];
+        match (self.accidental, interval) {
+            (Accidental::Sharp, _) | (Accidental::Natural, 6) => raised,
+            _ => lowered,
+        }
+    }
+
+    //Reads a note in the naming system, see parse_pitch
+    pub fn parse(s: &str, naming: Naming, key: Option<&Key>) -> Result<Note, Error> {
+        let (name, accidental, rest) = parse_pitch(s.trim(), naming, key.map(|key| &key.tonic))?;
+        let octave = match rest {
+            "" => 4,
+            octave => octave
+                .parse::<u8>()
+                .map_err(|_| format!("Invalid octave in note \"{s}\""))?,
+        };
+        Ok(Note::new(name, accidental, octave))
+    }
+}
+
+//Notes of the keys (spelled with sharps) for the tests, the keys below A0 are left out
+#[cfg(test)]
+pub(crate) fn notes(key_numbers: &[u8]) -> Vec<Note> {
+    key_numbers
+        .iter()
+        .filter_map(|key| Note::from(*key).map(|(note, _)| note))
+        .collect()
 }
 
 impl From<&Note> for u8 {
@@ -154,3 +252,255 @@ impl From<&Note> for u8 {
         number
     }
 }
+
+fn parse_accidental(s: &str) -> (Accidental, &str) {
+    match s.chars().next() {
+        Some(c @ ('#' | '♯')) => (Accidental::Sharp, &s[c.len_utf8()..]),
+        Some(c @ ('b' | '♭')) => (Accidental::Flat, &s[c.len_utf8()..]),
+        _ => (Accidental::Natural, s),
+    }
+}
+
+fn parse_letter(c: char) -> Option<Name> {
+    match c.to_ascii_uppercase() {
+        'A' => Some(Name::A),
+        'B' => Some(Name::B),
+        'C' => Some(Name::C),
+        'D' => Some(Name::D),
+        'E' => Some(Name::E),
+        'F' => Some(Name::F),
+        'G' => Some(Name::G),
+        _ => None,
+    }
+}
+
+//German sharps and flats ("Fis", "Es", "As", "Ces", "His")
+fn parse_german(s: &str) -> Option<(Name, Accidental, &str)> {
+    let mut chars = s.chars();
+    let name = match chars.next()?.to_ascii_uppercase() {
+        'H' => Name::B,
+        'B' => return None,
+        c => parse_letter(c)?,
+    };
+    let rest = chars.as_str();
+    let lower = rest.to_lowercase();
+    let suffix = |suffix: &str| lower.starts_with(suffix).then(|| &rest[suffix.len()..]);
+    if let Some(rest) = suffix("is") {
+        return Some((name, Accidental::Sharp, rest));
+    }
+    let flat = match name {
+        Name::A | Name::E => suffix("s"),
+        _ => suffix("es"),
+    };
+    flat.map(|rest| (name, Accidental::Flat, rest))
+}
+
+//Solfège syllables and their accidentals ("Sol#", "Ré♭"), from C or from the tonic in movable-do
+fn parse_syllable<'a>(
+    s: &'a str,
+    naming: Naming,
+    tonic: Option<&Note>,
+) -> Option<(Name, Accidental, &'a str)> {
+    let starts_with = |syllable: &str| {
+        s.get(..syllable.len())
+            .is_some_and(|start| start.to_lowercase() == syllable.to_lowercase())
+    };
+
+    if naming == Naming::MovableDo {
+        let tonic = tonic.map_or(0, |tonic| tonic.pitch_class());
+        let (interval, syllable, raised) = MOVABLE_SYLLABLES
+            .iter()
+            .enumerate()
+            .flat_map(|(i, (raised, lowered))| [(i, *raised, true), (i, *lowered, false)])
+            .filter(|(_, syllable, _)| starts_with(syllable))
+            .max_by_key(|(_, syllable, _)| syllable.len())?;
+        let (sharp, flat) = Note::from(60 + (tonic + interval as u8) % 12)?;
+        let note = match flat {
+            Some(flat) if !raised => flat,
+            _ => sharp,
+        };
+        return Some((note.name, note.accidental, &s[syllable.len()..]));
+    }
+
+    let names = [
+        Name::A,
+        Name::B,
+        Name::C,
+        Name::D,
+        Name::E,
+        Name::F,
+        Name::G,
+    ];
+    let (name, syllable) = names
+        .iter()
+        .map(|name| (*name, name.syllable()))
+        .chain([(Name::D, "Re"), (Name::B, "Ti")])
+        .find(|(_, syllable)| starts_with(syllable))?;
+    let (accidental, rest) = parse_accidental(&s[syllable.len()..]);
+    Some((name, accidental, rest))
+}
+
+//Reads a note name and its accidentals ("C", "F#", "Bb", "E♭"), returns the rest of the string.
+//Letters are read in every naming, german names ("Fis", "Es", "H", B for B♭) only in german and
+//syllables ("Sol#", "Ré♭") only in solfège, movable-do syllables are read from the tonic
+fn parse_pitch<'a>(
+    s: &'a str,
+    naming: Naming,
+    tonic: Option<&Note>,
+) -> Result<(Name, Accidental, &'a str), String> {
+    let pitch = match naming {
+        Naming::English => None,
+        Naming::German => parse_german(s),
+        Naming::FixedDo | Naming::MovableDo => parse_syllable(s, naming, tonic),
+    };
+    if let Some(pitch) = pitch {
+        return Ok(pitch);
+    }
+
+    let mut chars = s.chars();
+    let (name, natural) = match chars.next() {
+        Some('H' | 'h') if naming == Naming::German => (Name::B, Accidental::Natural),
+        Some('B' | 'b') if naming == Naming::German => (Name::B, Accidental::Flat),
+        Some(c) => match parse_letter(c) {
+            Some(name) => (name, Accidental::Natural),
+            None => return Err(format!("Invalid note name \"{s}\"")),
+        },
+        None => return Err(format!("Invalid note name \"{s}\"")),
+    };
+    match parse_accidental(chars.as_str()) {
+        (Accidental::Natural, rest) => Ok((name, natural, rest)),
+        (accidental, rest) => Ok((name, accidental, rest)),
+    }
+}
+
+impl FromStr for Note {
+    type Err = String;
+
+    //Octave defaults to 4 when omitted
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Note::parse(s, Naming::English, None).map_err(|e| e.to_string())
+    }
+}
+
+impl Key {
+    pub fn scale(&self) -> [u8; 7] {
+        let intervals = match self.mode {
+            Mode::Major => [0, 2, 4, 5, 7, 9, 11],
+            Mode::Minor => [0, 2, 3, 5, 7, 8, 10],
+        };
+        intervals.map(|interval| (self.tonic.pitch_class() + interval) % 12)
+    }
+
+    //Scale degree (0 for the tonic) of a pitch class, None when it is out of the key
+    pub fn degree(&self, pitch_class: u8) -> Option<usize> {
+        let degree = self.scale().iter().position(|pc| *pc == pitch_class);
+        //Leading tone of the harmonic minor
+        if degree.is_none()
+            && self.mode == Mode::Minor
+            && pitch_class == (self.tonic.pitch_class() + 11) % 12
+        {
+            return Some(6);
+        }
+        degree
+    }
+}
+
+impl Key {
+    //The tonic of a movable-do key is read in fixed-do
+    pub fn parse(s: &str, naming: Naming) -> Result<Key, Error> {
+        let naming = match naming {
+            Naming::MovableDo => Naming::FixedDo,
+            naming => naming,
+        };
+        let (name, accidental, rest) = parse_pitch(s.trim(), naming, None)?;
+        let mode = match rest.trim().to_lowercase().as_str() {
+            "" | "maj" | "major" => Mode::Major,
+            "m" | "min" | "minor" => Mode::Minor,
+            _ => {
+                return Err(Error::Parse(format!(
+                    "Invalid key \"{s}\" (examples: C, F#m, Bb, Ebmin)"
+                )))
+            }
+        };
+        Ok(Key {
+            tonic: Note::new(name, accidental, 4),
+            mode,
+        })
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Key::parse(s, Naming::English).map_err(|e| e.to_string())
+    }
+}
+
+impl FromStr for Naming {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "english" => Ok(Naming::English),
+            "german" => Ok(Naming::German),
+            "fixed_do" | "solfege" => Ok(Naming::FixedDo),
+            "movable_do" => Ok(Naming::MovableDo),
+            _ => Err(format!(
+                "Unknown naming \"{s}\" (available: english, german, fixed_do, movable_do)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Naming {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Naming::English => write!(f, "english"),
+            Naming::German => write!(f, "german"),
+            Naming::FixedDo => write!(f, "fixed_do"),
+            Naming::MovableDo => write!(f, "movable_do"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn german_names_are_read_in_german_only() {
+        let a_flat_minor = Key {
+            tonic: Note::new(Name::A, Accidental::Flat, 4),
+            mode: Mode::Minor,
+        };
+        assert!(Key::parse("Asmin", Naming::German).ok() == Some(a_flat_minor));
+        assert!(Key::parse("Abmin", Naming::English).ok() == Some(a_flat_minor));
+        assert!(Key::parse("Asmin", Naming::English).is_err());
+        assert!(Note::parse("Es", Naming::English, None).is_err());
+        assert!(Note::parse("H2", Naming::English, None).is_err());
+        assert!(
+            Note::parse("H2", Naming::German, None).ok()
+                == Some(Note::new(Name::B, Accidental::Natural, 2))
+        );
+    }
+
+    #[test]
+    fn syllables_are_read_in_solfege_only() {
+        assert!(Note::parse("Sol", Naming::English, None).is_err());
+        assert!(Note::parse("Si", Naming::English, None).is_err());
+        assert!(
+            Note::parse("Sol", Naming::FixedDo, None).ok()
+                == Some(Note::new(Name::G, Accidental::Natural, 4))
+        );
+        assert!(
+            Note::parse("Ré♭3", Naming::FixedDo, None).ok()
+                == Some(Note::new(Name::D, Accidental::Flat, 3))
+        );
+        //Letters are read in every naming
+        assert!(
+            Note::parse("F#3", Naming::FixedDo, None).ok()
+                == Some(Note::new(Name::F, Accidental::Sharp, 3))
+        );
+    }
+}
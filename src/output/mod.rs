@@ -0,0 +1,468 @@
+use crate::{
+    chord::{Chord, Stacking},
+    config::Config,
+    midi::PlayedNote,
+    note::Note,
+    scale::{self, ChordScale, Scale, ScaleMode},
+    tempo::Position,
+    voice_leading::{Analysis, Motion, Voice, Warning},
+};
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("Unknown format \"{s}\" (available: text, json)")),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::Text => write!(f, "text"),
+            Format::Json => write!(f, "json"),
+        }
+    }
+}
+
+//The channel is given when channels are analysed separately, the position once the tempo is known
+pub fn print_report(
+    config: &Config,
+    notes: &[PlayedNote],
+    chords: &[Chord],
+    scales: &[Scale],
+    voice_leading: Option<&Analysis>,
+    channel: Option<u8>,
+    position: Option<Position>,
+) {
+    match config.format {
+        Format::Text => {
+            match (channel, position) {
+                (Some(channel), Some(position)) => {
+                    println!("[Channel {}, {position}]", channel + 1)
+                }
+                (Some(channel), None) => println!("[Channel {}]", channel + 1),
+                (None, Some(position)) => println!("[{position}]"),
+                (None, None) => (),
+            }
+            print_text(config, notes, chords, scales);
+            if let Some(analysis) = voice_leading {
+                print_voice_leading(config, analysis);
+            }
+        }
+        Format::Json => println!(
+            "{}",
+            to_json(
+                config,
+                notes,
+                chords,
+                scales,
+                voice_leading,
+                channel,
+                position
+            )
+        ),
+    }
+}
+
+fn print_text(config: &Config, notes: &[PlayedNote], chords: &[Chord], scales: &[Scale]) {
+    let notation = config.notation();
+
+    if notes.is_empty() {
+        println!("-");
+        return;
+    }
+
+    //"C4(mf) E4(f ped)", with a split the hands are separated: "C3(mf) | E4(f)"
+    let mut line: Vec<String> = vec![];
+    for (i, played) in notes.iter().enumerate() {
+        if i > 0 && is_left_hand(config, &notes[i - 1]) && !is_left_hand(config, played) {
+            line.push("|".to_string());
+        }
+        let name = notation.notes(&[(played.note, played.flat_note)]);
+        match played.state.pedal_held {
+            true => line.push(format!("{name}({} ped)", played.state.dynamic())),
+            false => line.push(format!("{name}({})", played.state.dynamic())),
+        }
+    }
+    println!("{}", line.join(" "));
+
+    //"D Dorian (7/7)", played notes out of the scale notes
+    if !scales.is_empty() {
+        print!("Scales: ");
+        for scale in scales {
+            let length = scale.intervals.len();
+            let played = length - scale.missing(&to_notes(notes));
+            print!("\n\t{} ({played}/{length})", notation.scale(scale));
+        }
+        println!();
+    }
+    if config.scales == ScaleMode::Only {
+        return;
+    }
+
+    if chords.is_empty() {
+        println!("No chord found");
+        return;
+    }
+
+    print!("Chords: ");
+    for chord in chords {
+        print!("\n\t{} [{}]", notation.chord(chord), chord.weight());
+        if let Some(inversion) = chord.inversion() {
+            match chord.figured_bass() {
+                Some(figures) => print!(" {inversion} ({figures})"),
+                None => print!(" {inversion}"),
+            }
+        }
+        if config.explain {
+            print!("\n\t\t{}", chord.score);
+        }
+    }
+    println!();
+
+    //"D Dorian : tensions 9, 11, 13 / avoid -"
+    if let (true, Some(chord)) = (config.chord_scales, chords.first()) {
+        print!("Chord scales ({}): ", notation.chord(chord));
+        for chord_scale in scale::chord_scales(chord, &to_notes(notes), config.scoring.key.as_ref())
+        {
+            print!(
+                "\n\t{} : tensions {} / avoid {}",
+                notation.scale(&chord_scale.scale),
+                degrees(config, &chord_scale.tensions).join(", "),
+                degrees(config, &chord_scale.avoid).join(", ")
+            );
+        }
+        println!();
+    }
+
+    if let (Some(tuning), Some(chord)) = (&config.fretboard, chords.first()) {
+        print!(
+            "Fretboard ({}, {}): ",
+            notation.chord(chord),
+            tuning.name(&notation)
+        );
+        let fingerings = tuning.fingerings(chord);
+        if fingerings.is_empty() {
+            print!("no fingering found");
+        }
+        for fingering in fingerings {
+            print!("\n\t{}", fingering.compact());
+            for line in fingering.diagram() {
+                print!("\n\t{line}");
+            }
+        }
+        println!();
+    }
+}
+
+//Degrees of a chord-scale, "-" when there is none
+fn degrees(config: &Config, intervals: &[u8]) -> Vec<String> {
+    match intervals.is_empty() {
+        true => vec!["-".to_string()],
+        false => intervals
+            .iter()
+            .map(|interval| config.notation().degree(*interval))
+            .collect(),
+    }
+}
+
+fn chord_scale_json(config: &Config, chord_scale: &ChordScale) -> String {
+    let tensions: Vec<String> = chord_scale
+        .tensions
+        .iter()
+        .map(|interval| json_string(&config.notation().degree(*interval)))
+        .collect();
+    let avoid: Vec<String> = chord_scale
+        .avoid
+        .iter()
+        .map(|interval| json_string(&config.notation().degree(*interval)))
+        .collect();
+    format!(
+        "{{\"name\":{},\"tensions\":[{}],\"avoid\":[{}]}}",
+        json_string(&config.notation().scale(&chord_scale.scale)),
+        tensions.join(","),
+        avoid.join(",")
+    )
+}
+
+fn to_json(
+    config: &Config,
+    notes: &[PlayedNote],
+    chords: &[Chord],
+    scales: &[Scale],
+    voice_leading: Option<&Analysis>,
+    channel: Option<u8>,
+    position: Option<Position>,
+) -> String {
+    let notation = config.notation();
+
+    let played_notes = to_notes(notes);
+    let notes: Vec<String> = notes
+        .iter()
+        .map(|played| {
+            let name = notation.notes(&[(played.note, played.flat_note)]);
+            let mut fields = vec![
+                format!("\"name\":{}", json_string(&name)),
+                format!("\"velocity\":{}", played.state.velocity),
+                format!("\"dynamic\":{}", json_string(played.state.dynamic())),
+                format!("\"pressed_at\":{}", played.state.pressed_at),
+                format!("\"channel\":{}", played.state.channel + 1),
+                format!("\"pedal\":{}", played.state.pedal_held),
+            ];
+            if config.split.is_some() {
+                let hand = match is_left_hand(config, played) {
+                    true => "left",
+                    false => "right",
+                };
+                fields.push(format!("\"hand\":{}", json_string(hand)));
+            }
+            format!("{{{}}}", fields.join(","))
+        })
+        .collect();
+
+    let best_chord = chords.first();
+    let chords: Vec<String> = chords
+        .iter()
+        .map(|chord| {
+            let mut fields = vec![
+                format!("\"name\":{}", json_string(&notation.chord(chord))),
+                format!("\"weight\":{}", chord.weight()),
+                format!("\"root\":{}", json_string(&notation.pitch(&chord.root))),
+            ];
+            if let Some(bass) = &chord.bass {
+                fields.push(format!("\"bass\":{}", json_string(&notation.pitch(bass))));
+            }
+            if let Some(upper) = &chord.upper {
+                let stacking = match upper.stacking {
+                    Stacking::Polychord => "polychord",
+                    Stacking::UpperStructure => "upper_structure",
+                };
+                fields.push(format!(
+                    "\"upper\":{}",
+                    json_string(&notation.chord(&upper.chord))
+                ));
+                fields.push(format!("\"stacking\":{}", json_string(stacking)));
+            }
+            if let Some(inversion) = chord.inversion() {
+                fields.push(format!("\"inversion\":{}", json_string(inversion.name())));
+            }
+            if let Some(figures) = chord.figured_bass() {
+                fields.push(format!("\"figures\":{}", json_string(figures)));
+            }
+            if config.explain {
+                let components: Vec<String> = chord
+                    .score
+                    .components()
+                    .iter()
+                    .map(|(component, points)| format!("\"{}\":{points}", component.name()))
+                    .collect();
+                fields.push(format!("\"score\":{{{}}}", components.join(",")));
+            }
+            format!("{{{}}}", fields.join(","))
+        })
+        .collect();
+
+    let scales: Vec<String> = scales
+        .iter()
+        .map(|scale| {
+            format!(
+                "{{\"name\":{},\"root\":{},\"missing\":{}}}",
+                json_string(&notation.scale(scale)),
+                json_string(&notation.pitch(&scale.root)),
+                scale.missing(&played_notes)
+            )
+        })
+        .collect();
+
+    let mut fields = vec![];
+    if let Some(channel) = channel {
+        fields.push(format!("\"channel\":{}", channel + 1));
+    }
+    if let Some(position) = position {
+        fields.push(format!("\"bar\":{}", position.bar));
+        fields.push(format!("\"beat\":{}", position.beat));
+        fields.push(format!("\"bpm\":{}", position.bpm));
+    }
+    fields.push(format!("\"notes\":[{}]", notes.join(",")));
+    if config.scales != ScaleMode::Only {
+        fields.push(format!("\"chords\":[{}]", chords.join(",")));
+        if let (true, Some(chord)) = (config.chord_scales, best_chord) {
+            let chord_scales: Vec<String> =
+                scale::chord_scales(chord, &played_notes, config.scoring.key.as_ref())
+                    .iter()
+                    .map(|chord_scale| chord_scale_json(config, chord_scale))
+                    .collect();
+            fields.push(format!("\"chord_scales\":[{}]", chord_scales.join(",")));
+        }
+        if let (Some(tuning), Some(chord)) = (&config.fretboard, best_chord) {
+            let fingerings: Vec<String> = tuning
+                .fingerings(chord)
+                .iter()
+                .map(|fingering| json_string(&fingering.compact()))
+                .collect();
+            fields.push(format!("\"fingerings\":[{}]", fingerings.join(",")));
+        }
+    }
+    if !scales.is_empty() {
+        fields.push(format!("\"scales\":[{}]", scales.join(",")));
+    }
+    if let Some(analysis) = voice_leading {
+        fields.push(format!(
+            "\"voice_leading\":{}",
+            voice_leading_json(config, analysis)
+        ));
+    }
+    format!("{{{}}}", fields.join(","))
+}
+
+fn motion_name(motion: Motion) -> &'static str {
+    match motion {
+        Motion::Common => "common",
+        Motion::Step => "step",
+        Motion::Leap => "leap",
+        Motion::Enters => "enters",
+        Motion::Leaves => "leaves",
+    }
+}
+
+//"B3 → C4 (+1)", "- → E4" for a voice entering
+fn voice_name(config: &Config, voice: &Voice) -> String {
+    let note =
+        |note: Option<Note>| note.map_or("-".to_string(), |note| config.notation().note(&note));
+    let mut name = format!("{} → {}", note(voice.from), note(voice.to));
+    if let Some(movement) = voice.movement() {
+        name += &format!(" ({movement:+})");
+    }
+    name
+}
+
+fn warning_text(config: &Config, warning: &Warning) -> String {
+    match warning {
+        Warning::ParallelFifths(lower, upper) => format!(
+            "parallel fifths {} / {}",
+            voice_name(config, lower),
+            voice_name(config, upper)
+        ),
+        Warning::ParallelOctaves(lower, upper) => format!(
+            "parallel octaves {} / {}",
+            voice_name(config, lower),
+            voice_name(config, upper)
+        ),
+        Warning::UnresolvedLeadingTone(voice) => {
+            format!("unresolved leading tone {}", voice_name(config, voice))
+        }
+        Warning::UnresolvedSeventh(voice) => {
+            format!("unresolved seventh {}", voice_name(config, voice))
+        }
+    }
+}
+
+//"Voice leading: 2 common, 2 steps, 1 leap, 5 semitones" then each voice and the warnings
+fn print_voice_leading(config: &Config, analysis: &Analysis) {
+    let mut counts = vec![];
+    for motion in [
+        Motion::Common,
+        Motion::Step,
+        Motion::Leap,
+        Motion::Enters,
+        Motion::Leaves,
+    ] {
+        let count = analysis.count(motion);
+        let name = match motion {
+            Motion::Step | Motion::Leap if count > 1 => format!("{}s", motion_name(motion)),
+            Motion::Enters => "entering".to_string(),
+            Motion::Leaves => "leaving".to_string(),
+            _ => motion_name(motion).to_string(),
+        };
+        if count > 0 {
+            counts.push(format!("{count} {name}"));
+        }
+    }
+    print!(
+        "Voice leading: {}, {} semitones",
+        counts.join(", "),
+        analysis.total_movement()
+    );
+    for voice in &analysis.voices {
+        print!("\n\t{}", voice_name(config, voice));
+    }
+    for warning in &analysis.warnings {
+        print!("\n\t! {}", warning_text(config, warning));
+    }
+    println!();
+}
+
+fn voice_leading_json(config: &Config, analysis: &Analysis) -> String {
+    let note = |note: Option<Note>| {
+        note.map_or("null".to_string(), |note| {
+            json_string(&config.notation().note(&note))
+        })
+    };
+    let voices: Vec<String> = analysis
+        .voices
+        .iter()
+        .map(|voice| {
+            let mut fields = vec![
+                format!("\"from\":{}", note(voice.from)),
+                format!("\"to\":{}", note(voice.to)),
+                format!("\"motion\":{}", json_string(motion_name(voice.motion()))),
+            ];
+            if let Some(movement) = voice.movement() {
+                fields.push(format!("\"semitones\":{movement}"));
+            }
+            format!("{{{}}}", fields.join(","))
+        })
+        .collect();
+    let warnings: Vec<String> = analysis
+        .warnings
+        .iter()
+        .map(|warning| json_string(&warning_text(config, warning)))
+        .collect();
+    format!(
+        "{{\"voices\":[{}],\"semitones\":{},\"warnings\":[{}]}}",
+        voices.join(","),
+        analysis.total_movement(),
+        warnings.join(",")
+    )
+}
+
+fn to_notes(notes: &[PlayedNote]) -> Vec<Note> {
+    notes.iter().map(|played| played.note).collect()
+}
+
+fn is_left_hand(config: &Config, played: &PlayedNote) -> bool {
+    config
+        .split
+        .is_some_and(|split| u8::from(&played.note) < u8::from(&split))
+}
+
+pub fn json_string(value: &str) -> String {
+    let mut result = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => result += "\\\"",
+            '\\' => result += "\\\\",
+            '\n' => result += "\\n",
+            '\t' => result += "\\t",
+            c if (c as u32) < 0x20 => result += &format!("\\u{:04x}", c as u32),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
@@ -0,0 +1,338 @@
+use crate::{
+    chord::{self, Chord},
+    config::Config,
+    note::Note,
+};
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+//Chords asked: name in the report, semitones from the root and first level asking it
+const QUALITIES: [(&str, &[u8], Level); 15] = [
+    ("major", &[0, 4, 7], Level::Easy),
+    ("minor", &[0, 3, 7], Level::Easy),
+    ("diminished", &[0, 3, 6], Level::Medium),
+    ("augmented", &[0, 4, 8], Level::Medium),
+    ("sus4", &[0, 5, 7], Level::Medium),
+    ("sus2", &[0, 2, 7], Level::Medium),
+    ("dominant 7th", &[0, 4, 7, 10], Level::Medium),
+    ("major 7th", &[0, 4, 7, 11], Level::Medium),
+    ("minor 7th", &[0, 3, 7, 10], Level::Medium),
+    ("half-diminished", &[0, 3, 6, 10], Level::Hard),
+    ("diminished 7th", &[0, 3, 6, 9], Level::Hard),
+    ("minor-major 7th", &[0, 3, 7, 11], Level::Hard),
+    ("dominant 9th", &[0, 4, 7, 10, 14], Level::Hard),
+    ("major 9th", &[0, 4, 7, 11, 14], Level::Hard),
+    ("minor 9th", &[0, 3, 7, 10, 14], Level::Hard),
+];
+
+//Key number of the lowest root asked (middle C)
+const LOWEST_ROOT: u8 = 60;
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum Level {
+    //Major and minor triads in root position
+    Easy,
+    //Altered triads, suspensions, seventh chords and inverted triads
+    Medium,
+    //Every chord in any inversion, ninth chords in root position
+    Hard,
+}
+
+//Xorshift, enough to shuffle the questions
+struct Random(u64);
+
+impl Random {
+    fn new() -> Random {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        Random(seed | 1)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+//Chord to play, only its bass matters when it is shown inverted
+struct Question {
+    quality: usize,
+    notes: Vec<Note>,
+    //Named by the engine, answers are graded against it
+    target: Option<Chord>,
+    bass: Option<u8>,
+    symbol: String,
+    asked_at: Instant,
+    missed: bool,
+}
+
+//Answers given to one kind of chord
+#[derive(Clone, Copy, Default)]
+struct Tally {
+    asked: u32,
+    right: u32,
+    answered: u32,
+    seconds: f64,
+}
+
+pub struct Quiz {
+    level: Level,
+    random: Random,
+    question: Option<Question>,
+    //Keys of the last answer, releasing some of them is not another answer
+    held: Vec<u8>,
+    tallies: [Tally; QUALITIES.len()],
+}
+
+//Chord recognised in the answer, preferring the one built on the root asked
+fn recognised<'a>(chords: &'a [Chord], target: &Chord) -> Option<&'a Chord> {
+    chords
+        .iter()
+        .find(|chord| {
+            chord.root.pitch_class() == target.root.pitch_class() && chord.upper.is_none()
+        })
+        .or(chords.first())
+}
+
+//Same root, quality, suspension, extension and tensions, in any voicing unless the bass is asked
+fn is_answer(played: &Chord, target: &Chord, bass: Option<u8>) -> bool {
+    played.root.pitch_class() == target.root.pitch_class()
+        && played.quality == target.quality
+        && played.suspension == target.suspension
+        && played.extension == target.extension
+        && played.tensions == target.tensions
+        && played.upper.is_none()
+        && bass.is_none_or(|bass| {
+            played
+                .bass
+                .as_ref()
+                .map_or(played.root.pitch_class(), |note| note.pitch_class())
+                == bass
+        })
+}
+
+//Flats for the black keys but F#, as they are usually written
+fn spell(key_number: u8) -> Option<Note> {
+    let (sharp, flat) = Note::from(key_number)?;
+    match (key_number % 12, flat) {
+        (6, _) | (_, None) => Some(sharp),
+        (_, Some(flat)) => Some(flat),
+    }
+}
+
+impl Quiz {
+    pub fn new(level: Level) -> Quiz {
+        Quiz {
+            level,
+            random: Random::new(),
+            question: None,
+            held: vec![],
+            tallies: [Tally::default(); QUALITIES.len()],
+        }
+    }
+
+    //Picks the next chord and returns what to play
+    pub fn ask(&mut self, config: &Config) -> String {
+        let choices: Vec<usize> = (0..QUALITIES.len())
+            .filter(|i| QUALITIES[*i].2 <= self.level)
+            .collect();
+        let quality = choices[self.random.below(choices.len())];
+        let (_, intervals, _) = QUALITIES[quality];
+
+        let root = LOWEST_ROOT + self.random.below(12) as u8;
+        let inversions = match (self.level, intervals.len()) {
+            (Level::Easy, _) | (_, 5) => 1,
+            (Level::Medium, 3) => 3,
+            (Level::Medium, _) => 1,
+            (Level::Hard, voices) => voices,
+        };
+        let inversion = self.random.below(inversions);
+
+        //Inverted notes go up an octave, the bass is the lowest key
+        let mut key_numbers: Vec<u8> = intervals
+            .iter()
+            .enumerate()
+            .map(|(i, interval)| root + interval + if i < inversion { 12 } else { 0 })
+            .collect();
+        key_numbers.sort();
+        let notes: Vec<Note> = key_numbers.iter().filter_map(|key| spell(*key)).collect();
+
+        //The engine names the chord, keeping the root it was built on
+        let chords = chord::to_chord(&notes, &notes[0], &config.scoring);
+        let target = chords
+            .iter()
+            .find(|chord| chord.root.pitch_class() == root % 12 && chord.upper.is_none())
+            .or(chords.first());
+        let symbol = target.map_or("-".to_string(), |chord| config.notation().chord(chord));
+
+        self.question = Some(Question {
+            quality,
+            bass: (inversion > 0).then(|| notes[0].pitch_class()),
+            target: target.cloned(),
+            notes,
+            symbol: symbol.clone(),
+            asked_at: Instant::now(),
+            missed: false,
+        });
+
+        format!("[QUIZ] Play {symbol}")
+    }
+
+    //Checks the played notes against the question, the next one is asked once it is right
+    pub fn answer(&mut self, config: &Config, notes: &[Note], chords: &[Chord]) -> Option<String> {
+        let keys: Vec<u8> = notes.iter().map(u8::from).collect();
+        let released = keys.iter().all(|key| self.held.contains(key));
+        self.held = keys;
+        let question = self.question.as_mut()?;
+        if released {
+            return None;
+        }
+
+        let recognised = match &question.target {
+            Some(target) => recognised(chords, target),
+            None => chords.first(),
+        };
+        let played = match recognised {
+            Some(chord) => config.notation().chord(chord),
+            None => "-".to_string(),
+        };
+        let right = match (recognised, &question.target) {
+            (Some(chord), Some(target)) => is_answer(chord, target, question.bass),
+            _ => false,
+        };
+        if !right {
+            question.missed = true;
+            return Some(format!(
+                "[QUIZ] ✘ {played}, not {} (try again or \"quiz skip\")",
+                question.symbol
+            ));
+        }
+
+        let seconds = question.asked_at.elapsed().as_secs_f64();
+        let tally = &mut self.tallies[question.quality];
+        tally.asked += 1;
+        tally.answered += 1;
+        tally.seconds += seconds;
+        if !question.missed {
+            tally.right += 1;
+        }
+        let verdict = format!("[QUIZ] ✔ {} in {seconds:.1}s", question.symbol);
+        Some(format!("{verdict}\n{}", self.ask(config)))
+    }
+
+    //Gives up the question, it counts as missed
+    pub fn skip(&mut self, config: &Config) -> String {
+        let answer = match &self.question {
+            Some(question) => {
+                self.tallies[question.quality].asked += 1;
+                let names: Vec<String> = question
+                    .notes
+                    .iter()
+                    .map(|note| config.notation().note(note))
+                    .collect();
+                format!("[QUIZ] {} is {}", question.symbol, names.join(" "))
+            }
+            None => String::new(),
+        };
+        format!("{answer}\n{}", self.ask(config))
+    }
+
+    //Chords played right the first time and the time taken to find them, in total and by chord
+    pub fn report(&self) -> String {
+        let mut lines = vec![];
+        let mut total = Tally::default();
+        for (i, tally) in self.tallies.iter().enumerate() {
+            if tally.asked == 0 {
+                continue;
+            }
+            lines.push(format!("\t{}: {}", QUALITIES[i].0, tally));
+            total.asked += tally.asked;
+            total.right += tally.right;
+            total.answered += tally.answered;
+            total.seconds += tally.seconds;
+        }
+        lines.insert(0, format!("Score: {total}"));
+        lines.join("\n")
+    }
+}
+
+impl Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let accuracy = self.right as f64 * 100.0 / self.asked.max(1) as f64;
+        write!(f, "{}/{} ({accuracy:.0}%)", self.right, self.asked)?;
+        if self.answered > 0 {
+            write!(f, ", {:.1}s per chord", self.seconds / self.answered as f64)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Level::Easy),
+            "medium" => Ok(Level::Medium),
+            "hard" => Ok(Level::Hard),
+            _ => Err(format!(
+                "Unknown quiz level \"{s}\" (available: easy, medium, hard)"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notes(key_numbers: &[u8]) -> Vec<Note> {
+        key_numbers.iter().filter_map(|key| spell(*key)).collect()
+    }
+
+    //Plays the keys as the keyboard would, naming them on the lowest one
+    fn play(quiz: &mut Quiz, config: &Config, key_numbers: &[u8]) -> String {
+        let notes = notes(key_numbers);
+        let chords = chord::to_chord(&notes, &notes[0], &config.scoring);
+        let verdict = quiz.answer(config, &notes, &chords).unwrap();
+        quiz.held.clear();
+        verdict
+    }
+
+    //Asks until the question is the wanted chord in root position
+    fn ask(quiz: &mut Quiz, config: &Config, symbol: &str) {
+        while quiz
+            .question
+            .as_ref()
+            .is_none_or(|question| question.symbol != symbol || question.bass.is_some())
+        {
+            quiz.ask(config);
+        }
+    }
+
+    #[test]
+    fn any_voicing_of_the_chord_is_right() {
+        let config = Config::default();
+        let mut quiz = Quiz::new(Level::Medium);
+        ask(&mut quiz, &config, "G7");
+        assert!(play(&mut quiz, &config, &[43, 59, 62, 65, 67]).starts_with("[QUIZ] ✔ G7"));
+        ask(&mut quiz, &config, "C");
+        assert!(play(&mut quiz, &config, &[52, 60, 67]).starts_with("[QUIZ] ✔ C"));
+    }
+
+    #[test]
+    fn another_chord_is_wrong() {
+        let config = Config::default();
+        let mut quiz = Quiz::new(Level::Medium);
+        ask(&mut quiz, &config, "G7");
+        assert!(play(&mut quiz, &config, &[55, 59, 62]).starts_with("[QUIZ] ✘ G, not G7"));
+        ask(&mut quiz, &config, "Csus4");
+        assert!(play(&mut quiz, &config, &[60, 64, 65, 67]).starts_with("[QUIZ] ✘"));
+    }
+}
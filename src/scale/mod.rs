@@ -0,0 +1,307 @@
+use crate::{
+    chord::Chord,
+    note::{Key, Note},
+};
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+//Distinct pitch classes from which scales are shown in auto mode
+const SCALE_PITCH_CLASSES: u32 = 6;
+
+//Scales shown for the held notes
+const MAX_SCALES: usize = 5;
+
+//Chord-scales suggested for a chord
+const MAX_CHORD_SCALES: usize = 3;
+
+//Modes of the major, melodic minor and harmonic minor scales and the symmetric scales can
+//be played over a chord, pentatonic and bebop scales are only recognised
+const CHORD_SCALES: usize = 24;
+
+//Scales by family, the usual ones first: name and semitones from the root
+const SCALES: [(&str, &[u8]); 29] = [
+    ("Ionian", &[0, 2, 4, 5, 7, 9, 11]),
+    ("Dorian", &[0, 2, 3, 5, 7, 9, 10]),
+    ("Phrygian", &[0, 1, 3, 5, 7, 8, 10]),
+    ("Lydian", &[0, 2, 4, 6, 7, 9, 11]),
+    ("Mixolydian", &[0, 2, 4, 5, 7, 9, 10]),
+    ("Aeolian", &[0, 2, 3, 5, 7, 8, 10]),
+    ("Locrian", &[0, 1, 3, 5, 6, 8, 10]),
+    ("melodic minor", &[0, 2, 3, 5, 7, 9, 11]),
+    ("Dorian ♭2", &[0, 1, 3, 5, 7, 9, 10]),
+    ("Lydian augmented", &[0, 2, 4, 6, 8, 9, 11]),
+    ("Lydian dominant", &[0, 2, 4, 6, 7, 9, 10]),
+    ("Mixolydian ♭6", &[0, 2, 4, 5, 7, 8, 10]),
+    ("Locrian ♮2", &[0, 2, 3, 5, 6, 8, 10]),
+    ("altered", &[0, 1, 3, 4, 6, 8, 10]),
+    ("harmonic minor", &[0, 2, 3, 5, 7, 8, 11]),
+    ("Locrian ♮6", &[0, 1, 3, 5, 6, 9, 10]),
+    ("Ionian ♯5", &[0, 2, 4, 5, 8, 9, 11]),
+    ("Dorian ♯4", &[0, 2, 3, 6, 7, 9, 10]),
+    ("Phrygian dominant", &[0, 1, 4, 5, 7, 8, 10]),
+    ("Lydian ♯2", &[0, 3, 4, 6, 7, 9, 11]),
+    ("altered ♭♭7", &[0, 1, 3, 4, 6, 8, 9]),
+    ("whole-tone", &[0, 2, 4, 6, 8, 10]),
+    ("half-whole diminished", &[0, 1, 3, 4, 6, 7, 9, 10]),
+    ("whole-half diminished", &[0, 2, 3, 5, 6, 8, 9, 11]),
+    ("major pentatonic", &[0, 2, 4, 7, 9]),
+    ("minor pentatonic", &[0, 3, 5, 7, 10]),
+    ("bebop dominant", &[0, 2, 4, 5, 7, 9, 10, 11]),
+    ("bebop major", &[0, 2, 4, 5, 7, 8, 9, 11]),
+    ("bebop dorian", &[0, 2, 3, 4, 5, 7, 9, 10]),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ScaleMode {
+    Off,
+    //Alongside the chords once enough notes are held
+    Auto,
+    //Alongside the chords
+    On,
+    //Instead of the chords
+    Only,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Scale {
+    pub root: Note,
+    pub name: &'static str,
+    pub intervals: &'static [u8],
+}
+
+impl Scale {
+    //Pitch classes of the scale as a bitmap
+    pub fn pitch_classes(&self) -> u16 {
+        self.intervals.iter().fold(0, |set, interval| {
+            set | (0b1 << ((self.root.pitch_class() + interval) % 12))
+        })
+    }
+
+    //Scale notes not among the held ones
+    pub fn missing(&self, notes: &[Note]) -> usize {
+        (self.pitch_classes() & !pitch_classes(notes)).count_ones() as usize
+    }
+}
+
+pub fn pitch_classes(notes: &[Note]) -> u16 {
+    notes
+        .iter()
+        .fold(0, |set, note| set | (0b1 << note.pitch_class()))
+}
+
+//Scales built on one of the notes and holding all of them, the closest fits first
+pub fn matching(notes: &[Note]) -> Vec<Scale> {
+    let held = pitch_classes(notes);
+    let Some(bass) = notes.first() else {
+        return vec![];
+    };
+
+    let mut scales = vec![];
+    let mut tried_roots: u16 = 0;
+    for root in notes {
+        if tried_roots & (0b1 << root.pitch_class()) != 0 {
+            continue;
+        }
+        tried_roots |= 0b1 << root.pitch_class();
+
+        for (name, intervals) in SCALES {
+            let scale = Scale {
+                root: *root,
+                name,
+                intervals,
+            };
+            if held & !scale.pitch_classes() == 0 {
+                scales.push(scale);
+            }
+        }
+    }
+
+    //Fewer notes left out, then the bass as root, then the usual scales
+    scales.sort_by_key(|scale| {
+        (
+            scale.missing(notes),
+            scale.root.pitch_class() != bass.pitch_class(),
+            SCALES.iter().position(|(name, _)| *name == scale.name),
+        )
+    });
+    scales.truncate(MAX_SCALES);
+    scales
+}
+
+//Scale to improvise over a chord, its notes that are not chord tones are either available
+//tensions or avoid notes (semitones from the root)
+pub struct ChordScale {
+    pub scale: Scale,
+    pub tensions: Vec<u8>,
+    pub avoid: Vec<u8>,
+}
+
+//Scales on the chord root holding every played note of the chord, the ones closest to the key
+//first. A fifth left out is not required, the altered scales of a dominant chord have none
+pub fn chord_scales(chord: &Chord, notes: &[Note], key: Option<&Key>) -> Vec<ChordScale> {
+    let played = notes
+        .iter()
+        .fold(0b1u16, |set, note| set | (0b1 << chord.interval(note)));
+    let chord_tones = chord.chord_tones() & played;
+    let needed = chord.intervals() & played;
+    let key_pitch_classes = key.map(|key| {
+        key.scale()
+            .iter()
+            .fold(0u16, |set, pitch_class| set | (0b1 << pitch_class))
+    });
+
+    let mut scales: Vec<(u32, ChordScale)> = vec![];
+    for (name, intervals) in &SCALES[..CHORD_SCALES] {
+        let scale_intervals = intervals
+            .iter()
+            .fold(0u16, |set, interval| set | (0b1 << interval));
+        if needed & !scale_intervals != 0 {
+            continue;
+        }
+
+        //A note a half step above a chord tone is avoided, but the ♭9 of a dominant chord
+        let dominant = chord_tones & (0b1 << 4 | 0b1 << 10) == 0b1 << 4 | 0b1 << 10;
+        let mut tensions = vec![];
+        let mut avoid = vec![];
+        for interval in intervals.iter().filter(|i| chord_tones & (0b1 << *i) == 0) {
+            let below = (interval + 11) % 12;
+            let clashes = chord_tones & (0b1 << below) != 0;
+            match clashes && !(*interval == 1 && dominant) {
+                true => avoid.push(*interval),
+                false => tensions.push(*interval),
+            }
+        }
+
+        let scale = Scale {
+            root: chord.root,
+            name,
+            intervals,
+        };
+        let out_of_key =
+            key_pitch_classes.map_or(0, |key| (scale.pitch_classes() & !key).count_ones());
+        scales.push((
+            out_of_key,
+            ChordScale {
+                scale,
+                tensions,
+                avoid,
+            },
+        ));
+    }
+
+    //Stable sort, the usual scales stay first among the ones as close to the key
+    scales.sort_by_key(|(out_of_key, _)| *out_of_key);
+    scales
+        .into_iter()
+        .take(MAX_CHORD_SCALES)
+        .map(|(_, chord_scale)| chord_scale)
+        .collect()
+}
+
+impl ScaleMode {
+    pub fn shows_scales(&self, notes: &[Note]) -> bool {
+        match self {
+            ScaleMode::Off => false,
+            ScaleMode::Auto => pitch_classes(notes).count_ones() >= SCALE_PITCH_CLASSES,
+            ScaleMode::On | ScaleMode::Only => true,
+        }
+    }
+}
+
+impl FromStr for ScaleMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(ScaleMode::Off),
+            "auto" => Ok(ScaleMode::Auto),
+            "on" => Ok(ScaleMode::On),
+            "only" => Ok(ScaleMode::Only),
+            _ => Err(format!(
+                "Unknown scale mode \"{s}\" (available: off, auto, on, only)"
+            )),
+        }
+    }
+}
+
+impl Display for ScaleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScaleMode::Off => write!(f, "off"),
+            ScaleMode::Auto => write!(f, "auto"),
+            ScaleMode::On => write!(f, "on"),
+            ScaleMode::Only => write!(f, "only"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chord, config::Config, note::notes};
+
+    //Scales holding the keys, the closest first
+    fn matching_names(key_numbers: &[u8]) -> Vec<String> {
+        let notation = Config::default().notation();
+        matching(&notes(key_numbers))
+            .iter()
+            .map(|scale| format!("{} {}", notation.pitch(&scale.root), scale.name))
+            .collect()
+    }
+
+    //Chord scales of the chord built on the lowest key
+    fn chord_scales_of(key_numbers: &[u8]) -> Vec<&'static str> {
+        let notes = notes(key_numbers);
+        let config = Config::default();
+        let chords = chord::to_chord(&notes, &notes[0], &config.scoring);
+        let chord = chords
+            .iter()
+            .find(|chord| chord.root == notes[0] && chord.upper.is_none())
+            .unwrap();
+        chord_scales(chord, &notes, None)
+            .iter()
+            .map(|chord_scale| chord_scale.scale.name)
+            .collect()
+    }
+
+    #[test]
+    fn dominant_without_fifth_gets_the_altered_scale() {
+        //G B F A♯
+        assert!(chord_scales_of(&[55, 59, 65, 70]).contains(&"altered"));
+    }
+
+    #[test]
+    fn played_fifth_is_required() {
+        //G B D F A♯
+        assert!(!chord_scales_of(&[55, 59, 62, 65, 70]).contains(&"altered"));
+    }
+
+    #[test]
+    fn bass_is_the_root_of_the_mode() {
+        //D E F A B C, the G left out
+        let names = matching_names(&[62, 64, 65, 69, 71, 72]);
+        assert_eq!(names[0], "D Dorian");
+        assert!(names.contains(&"C Ionian".to_string()));
+    }
+
+    #[test]
+    fn scales_holding_every_note_come_first() {
+        assert_eq!(
+            matching_names(&[60, 62, 64, 67, 69])[..2],
+            ["C major pentatonic", "A minor pentatonic"]
+        );
+        assert_eq!(
+            matching_names(&[57, 59, 60, 62, 64, 65, 68])[0],
+            "A harmonic minor"
+        );
+        assert_eq!(matching_names(&[60, 62, 64, 66, 68, 70])[0], "C whole-tone");
+    }
+
+    #[test]
+    fn scales_must_hold_every_note() {
+        //C D E F♯ G♯ A♯ B
+        assert!(matching_names(&[60, 62, 64, 66, 68, 70, 71]).is_empty());
+    }
+}
@@ -0,0 +1,253 @@
+use crate::note::Key;
+use std::fmt::{self, Display};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Component {
+    Quality,
+    MissingFifth,
+    Suspension,
+    Seventh,
+    AddedTension,
+    SlashBass,
+    KeyFit,
+    VoicingRegister,
+    Polychord,
+    UpperStructure,
+    Accent,
+    Split,
+}
+
+impl Component {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Component::Quality => "quality",
+            Component::MissingFifth => "missing_fifth",
+            Component::Suspension => "suspension",
+            Component::Seventh => "seventh",
+            Component::AddedTension => "added_tension",
+            Component::SlashBass => "slash_bass",
+            Component::KeyFit => "key_fit",
+            Component::VoicingRegister => "voicing_register",
+            Component::Polychord => "polychord",
+            Component::UpperStructure => "upper_structure",
+            Component::Accent => "accent",
+            Component::Split => "split",
+        }
+    }
+}
+
+//Coefficients of each score component, the lower the score the simpler the chord
+#[derive(Clone)]
+pub struct Weights {
+    pub quality: u32,
+    pub altered_quality: u32,
+    pub missing_fifth: u32,
+    pub suspension: u32,
+    pub altered_suspension: u32,
+    pub minor_suspension: u32,
+    pub seventh: u32,
+    pub major_seventh: u32,
+    pub added_tension: u32,
+    pub altered_tension: u32,
+    pub slash_bass: u32,
+    pub key_fit: u32,
+    pub voicing_register: u32,
+    pub polychord: u32,
+    pub upper_structure: u32,
+    pub accent: u32,
+    pub split: u32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            quality: 1,
+            altered_quality: 3,
+            missing_fifth: 3,
+            suspension: 4,
+            altered_suspension: 5,
+            minor_suspension: 5,
+            seventh: 4,
+            major_seventh: 5,
+            added_tension: 6,
+            altered_tension: 7,
+            slash_bass: 3,
+            key_fit: 2,
+            voicing_register: 2,
+            polychord: 12,
+            upper_structure: 3,
+            accent: 2,
+            split: 4,
+        }
+    }
+}
+
+const MAX_WEIGHT: u32 = 100;
+
+impl Weights {
+    fn fields(&self) -> [(&'static str, u32); 17] {
+        [
+            ("quality", self.quality),
+            ("altered_quality", self.altered_quality),
+            ("missing_fifth", self.missing_fifth),
+            ("suspension", self.suspension),
+            ("altered_suspension", self.altered_suspension),
+            ("minor_suspension", self.minor_suspension),
+            ("seventh", self.seventh),
+            ("major_seventh", self.major_seventh),
+            ("added_tension", self.added_tension),
+            ("altered_tension", self.altered_tension),
+            ("slash_bass", self.slash_bass),
+            ("key_fit", self.key_fit),
+            ("voicing_register", self.voicing_register),
+            ("polychord", self.polychord),
+            ("upper_structure", self.upper_structure),
+            ("accent", self.accent),
+            ("split", self.split),
+        ]
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let field = match name {
+            "quality" => &mut self.quality,
+            "altered_quality" => &mut self.altered_quality,
+            "missing_fifth" => &mut self.missing_fifth,
+            "suspension" => &mut self.suspension,
+            "altered_suspension" => &mut self.altered_suspension,
+            "minor_suspension" => &mut self.minor_suspension,
+            "seventh" => &mut self.seventh,
+            "major_seventh" => &mut self.major_seventh,
+            "added_tension" => &mut self.added_tension,
+            "altered_tension" => &mut self.altered_tension,
+            "slash_bass" => &mut self.slash_bass,
+            "key_fit" => &mut self.key_fit,
+            "voicing_register" => &mut self.voicing_register,
+            "polychord" => &mut self.polychord,
+            "upper_structure" => &mut self.upper_structure,
+            "accent" => &mut self.accent,
+            "split" => &mut self.split,
+            _ => return Err(format!("Unknown weight \"{name}\"")),
+        };
+        //Bounded so that the sums of the weights cannot overflow
+        *field = value
+            .parse()
+            .ok()
+            .filter(|weight| *weight <= MAX_WEIGHT)
+            .ok_or(format!(
+                "Invalid weight \"{value}\", expected an integer from 0 to {MAX_WEIGHT}"
+            ))?;
+        Ok(())
+    }
+}
+
+impl Display for Weights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields: Vec<String> = self
+            .fields()
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect();
+        write!(f, "{}", fields.join(" "))
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Scoring {
+    pub weights: Weights,
+    pub key: Option<Key>,
+}
+
+#[derive(Clone, PartialEq, Default)]
+pub struct Score {
+    components: Vec<(Component, u32)>,
+}
+
+impl Score {
+    pub fn add(&mut self, component: Component, points: u32) {
+        if points == 0 {
+            return;
+        }
+        match self.components.iter_mut().find(|(c, _)| *c == component) {
+            Some((_, total)) => *total += points,
+            None => self.components.push((component, points)),
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.components.iter().map(|(_, points)| points).sum()
+    }
+
+    pub fn components(&self) -> &[(Component, u32)] {
+        &self.components
+    }
+}
+
+//"quality 1 + seventh 4"
+impl Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.components.is_empty() {
+            return write!(f, "0");
+        }
+        let components: Vec<String> = self
+            .components
+            .iter()
+            .map(|(component, points)| format!("{} {points}", component.name()))
+            .collect();
+        write!(f, "{}", components.join(" + "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chord, chord::Chord, config::Config, note::notes};
+
+    fn ranking(config: &Config, key_numbers: &[u8]) -> Vec<Chord> {
+        let notes = notes(key_numbers);
+        chord::to_chord(&notes, &notes[0], &config.scoring)
+    }
+
+    #[test]
+    fn score_adds_up_by_component() {
+        let mut score = Score::default();
+        score.add(Component::Quality, 1);
+        score.add(Component::Seventh, 4);
+        score.add(Component::Seventh, 1);
+        score.add(Component::Accent, 0);
+        assert_eq!(score.total(), 6);
+        assert_eq!(score.to_string(), "quality 1 + seventh 5");
+    }
+
+    #[test]
+    fn chord_score_is_explained() {
+        let config = Config::default();
+        let chords = ranking(&config, &[57, 60, 64, 67]);
+        assert_eq!(config.notation().chord(&chords[0]), "Amin7");
+        assert_eq!(chords[0].score.to_string(), "quality 1 + seventh 4");
+        assert_eq!(chords[0].weight(), 5);
+    }
+
+    #[test]
+    fn weights_change_the_ranking() {
+        let mut config = Config::default();
+        let best = |config: &Config| {
+            config
+                .notation()
+                .chord(&ranking(config, &[60, 64, 67, 69])[0])
+        };
+        assert_eq!(best(&config), "C(13)");
+        config.set("weight.added_tension", "10").unwrap();
+        assert_eq!(best(&config), "Amin7/C");
+    }
+
+    #[test]
+    fn invalid_weights_are_rejected() {
+        let mut weights = Weights::default();
+        assert!(weights.set("sixth", "1").is_err());
+        assert!(weights.set("seventh", "-1").is_err());
+        assert!(weights.set("seventh", "4294967295").is_err());
+        assert!(weights.set("seventh", "100").is_ok());
+        assert!(weights.set("seventh", "2").is_ok());
+        assert!(weights.to_string().contains(" seventh=2 "));
+    }
+}
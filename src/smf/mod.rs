@@ -0,0 +1,286 @@
+//Standard MIDI files (format 0 and 1) are turned into timed messages, with a MIDI clock
+//following their tempo so the chords get their bar and beat
+
+//Microseconds per quarter note until a tempo event (120 bpm)
+const DEFAULT_TEMPO: f64 = 500_000.0;
+//The clock has 24 pulses per beat, longer files would hold too many of them
+const MAX_BEATS: u64 = 10_000;
+
+const CLOCK: u8 = 0xF8;
+const START: u8 = 0xFA;
+
+const META: u8 = 0xFF;
+const SET_TEMPO: u8 = 0x51;
+const SYSEX: u8 = 0xF0;
+const SYSEX_ESCAPE: u8 = 0xF7;
+
+pub fn is_smf(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"MThd")
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self
+            .bytes
+            .get(self.position)
+            .ok_or("Unexpected end of the midi file")?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + length)
+            .ok_or("Unexpected end of the midi file")?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn number(&mut self, length: usize) -> Result<u32, String> {
+        Ok(self
+            .take(length)?
+            .iter()
+            .fold(0, |number, byte| (number << 8) | *byte as u32))
+    }
+
+    //Variable length quantity, 7 bits per byte
+    fn quantity(&mut self) -> Result<u32, String> {
+        let mut quantity = 0;
+        for _ in 0..4 {
+            let byte = self.byte()?;
+            quantity = (quantity << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(quantity);
+            }
+        }
+        Err("Invalid variable length quantity in the midi file".to_string())
+    }
+
+    fn is_done(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+}
+
+//Channel messages with their tick, and tempo changes (tick, microseconds per quarter note)
+fn read_track(
+    track: &[u8],
+    events: &mut Vec<(u64, Vec<u8>)>,
+    tempos: &mut Vec<(u64, f64)>,
+) -> Result<(), String> {
+    let mut reader = Reader {
+        bytes: track,
+        position: 0,
+    };
+    let mut tick = 0;
+    let mut running_status = None;
+
+    while !reader.is_done() {
+        tick += reader.quantity()? as u64;
+
+        let mut status = reader.byte()?;
+        let first_data = match status {
+            0x80.. => None,
+            //Running status: the byte read is already the first data byte
+            data => {
+                status = running_status.ok_or("Missing status byte in the midi file")?;
+                Some(data)
+            }
+        };
+
+        match status {
+            META => {
+                let kind = reader.byte()?;
+                let length = reader.quantity()? as usize;
+                let data = reader.take(length)?;
+                if kind == SET_TEMPO && length == 3 {
+                    let tempo = data
+                        .iter()
+                        .fold(0, |tempo, byte| (tempo << 8) | *byte as u32);
+                    tempos.push((tick, tempo as f64));
+                }
+            }
+            SYSEX | SYSEX_ESCAPE => {
+                let length = reader.quantity()? as usize;
+                reader.take(length)?;
+            }
+            //Other system messages are not allowed in a midi file, their length is unknown
+            0xF1..=0xFE => {
+                return Err(format!(
+                    "Unexpected system message {status:02x} in the midi file"
+                ))
+            }
+            _ => {
+                running_status = Some(status);
+                let length = match status & 0xF0 {
+                    0xC0 | 0xD0 => 1,
+                    _ => 2,
+                };
+                let mut message = vec![status];
+                if let Some(data) = first_data {
+                    message.push(data);
+                }
+                while message.len() <= length {
+                    message.push(reader.byte()?);
+                }
+                if message[1..].iter().any(|data| *data >= 0x80) {
+                    return Err("Invalid data byte in the midi file".to_string());
+                }
+                events.push((tick, message));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn to_micros(tick: f64, tempos: &[(u64, f64)], division: f64) -> f64 {
+    let mut micros = 0.0;
+    let mut last_tick = 0.0;
+    let mut tempo = DEFAULT_TEMPO;
+
+    for (change, new_tempo) in tempos {
+        let change = *change as f64;
+        if change >= tick {
+            break;
+        }
+        micros += (change - last_tick) * tempo / division;
+        last_tick = change;
+        tempo = *new_tempo;
+    }
+
+    micros + (tick - last_tick) * tempo / division
+}
+
+pub fn parse(bytes: &[u8]) -> Result<Vec<(u64, Vec<u8>)>, String> {
+    let mut reader = Reader { bytes, position: 0 };
+
+    if reader.take(4)? != b"MThd" {
+        return Err("Not a standard midi file".to_string());
+    }
+    let header_length = reader.number(4)? as usize;
+    let header = reader.take(header_length)?;
+    if header.len() < 6 {
+        return Err("Invalid midi file header".to_string());
+    }
+    let format = u16::from_be_bytes([header[0], header[1]]);
+    let division = u16::from_be_bytes([header[4], header[5]]);
+    if format > 1 {
+        return Err(format!(
+            "Midi file format {format} is not supported (only 0 and 1)"
+        ));
+    }
+    if division & 0x8000 != 0 || division == 0 {
+        return Err("Midi files timed in SMPTE frames are not supported".to_string());
+    }
+
+    let mut events = vec![];
+    let mut tempos = vec![];
+    while !reader.is_done() {
+        let kind = reader.take(4)?;
+        let length = reader.number(4)? as usize;
+        let chunk = reader.take(length)?;
+        //Unknown chunks are skipped
+        if kind == b"MTrk" {
+            read_track(chunk, &mut events, &mut tempos)?;
+        }
+    }
+    tempos.sort_by_key(|(tick, _)| *tick);
+
+    let last_tick = events.iter().map(|(tick, _)| *tick).max().unwrap_or(0);
+    if last_tick / division as u64 > MAX_BEATS {
+        return Err(format!(
+            "Midi files longer than {MAX_BEATS} beats are not supported"
+        ));
+    }
+    let division = division as f64;
+    let last_tick = last_tick as f64;
+
+    //The clock runs one beat past the last event, its pulses come before the notes
+    let mut timed = vec![(0.0, 0, vec![START])];
+    let pulses = ((last_tick + division) * 24.0 / division) as u64;
+    for pulse in 0..=pulses {
+        let tick = pulse as f64 * division / 24.0;
+        timed.push((to_micros(tick, &tempos, division), 1, vec![CLOCK]));
+    }
+    for (tick, message) in events {
+        timed.push((to_micros(tick as f64, &tempos, division), 2, message));
+    }
+    timed.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+    Ok(timed
+        .into_iter()
+        .map(|(micros, _, message)| (micros.round() as u64, message))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Format 0 file of a single track, 96 ticks per beat
+    fn track_file(track: &[u8]) -> Vec<u8> {
+        let mut bytes = b"MThd\0\0\0\x06\0\0\0\x01\0\x60MTrk".to_vec();
+        bytes.extend((track.len() as u32).to_be_bytes());
+        bytes.extend(track);
+        bytes
+    }
+
+    //C4 held one beat then a tempo change to 60 bpm and E4
+    fn file() -> Vec<u8> {
+        track_file(&[
+            0x00, 0x90, 0x3C, 0x64, //
+            0x60, 0x80, 0x3C, 0x00, //
+            0x00, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40, //
+            0x60, 0x90, 0x40, 0x64, //
+            0x00, 0xFF, 0x2F, 0x00,
+        ])
+    }
+
+    #[test]
+    fn notes_follow_the_tempo_changes() {
+        let bytes = file();
+        assert!(is_smf(&bytes));
+        let notes: Vec<(u64, Vec<u8>)> = parse(&bytes)
+            .unwrap()
+            .into_iter()
+            .filter(|(_, message)| message[0] < 0xF0)
+            .collect();
+        assert_eq!(
+            notes,
+            [
+                (0, vec![0x90, 0x3C, 0x64]),
+                (500_000, vec![0x80, 0x3C, 0x00]),
+                (1_500_000, vec![0x90, 0x40, 0x64]),
+            ]
+        );
+    }
+
+    #[test]
+    fn clock_starts_the_file() {
+        let messages = parse(&file()).unwrap();
+        assert_eq!(messages[0], (0, vec![START]));
+        assert!(messages.iter().any(|(_, message)| message[0] == CLOCK));
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        assert!(!is_smf(b"0 90 3c 64"));
+        assert!(parse(b"MThd\0\0\0\x06\0\x02\0\x01\0\x60").is_err());
+    }
+
+    #[test]
+    fn malformed_tracks_are_rejected() {
+        //Key number above 127
+        assert!(parse(&track_file(&[0x00, 0x90, 0xBC, 0x64])).is_err());
+        //Song select, a system message without length
+        assert!(parse(&track_file(&[0x00, 0xF3, 0x01])).is_err());
+        //A note about 2.8 million beats after the start
+        assert!(parse(&track_file(&[0xFF, 0xFF, 0xFF, 0x7F, 0x90, 0x3C, 0x64])).is_err());
+    }
+}
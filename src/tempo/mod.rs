@@ -0,0 +1,264 @@
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+//MIDI clock resolution
+const PULSES_PER_BEAT: u64 = 24;
+
+//Song position pointers count sixteenth notes
+const PULSES_PER_SIXTEENTH: u64 = 6;
+
+//Without pulse for this long (microseconds), the clock is considered gone
+const CLOCK_TIMEOUT: u64 = 1_000_000;
+
+//Inferred beats are folded into this range (microseconds, 180 to 60 bpm)
+const SHORTEST_BEAT: u64 = 333_333;
+const LONGEST_BEAT: u64 = 1_000_000;
+
+//Onsets kept to infer the tempo
+const ONSETS: usize = 16;
+
+const CLOCK: u8 = 0x8;
+const START: u8 = 0xA;
+const CONTINUE: u8 = 0xB;
+const STOP: u8 = 0xC;
+const SONG_POSITION: u8 = 0x2;
+
+//Bar and beat (both from 1) at which a chord started
+#[derive(Clone, Copy, PartialEq)]
+pub struct Position {
+    pub bar: u64,
+    pub beat: u64,
+    pub bpm: u32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct TimeSignature {
+    pub beats: u8,
+    pub unit: u8,
+}
+
+//Follows the MIDI clock, or infers the beat from the chord onsets when there is none
+#[derive(Default)]
+pub struct Tempo {
+    running: bool,
+    //Number of the next clock pulse, from the start or the song position
+    next_pulse: u64,
+    //Stamp and number of the last pulse counted while running
+    last_pulse: Option<(u64, u64)>,
+    //Stamp of the last pulse, also received while stopped
+    last_tick: Option<u64>,
+    //Smoothed pulse length in microseconds
+    pulse_length: Option<u64>,
+    //Without clock the first onset is the first beat
+    first_onset: Option<u64>,
+    onsets: Vec<u64>,
+}
+
+impl Tempo {
+    //System messages carry their type where channel messages carry the channel
+    pub fn on_system_message(&mut self, stamp: u64, kind: u8, data: [u8; 2]) {
+        match kind {
+            //The tempo follows every pulse, the song position only moves while running
+            CLOCK => {
+                if self.has_clock(stamp) {
+                    let length = stamp.saturating_sub(self.last_tick.unwrap_or(stamp));
+                    self.pulse_length = Some(match self.pulse_length {
+                        Some(pulse_length) => (pulse_length * 7 + length) / 8,
+                        None => length,
+                    });
+                }
+                self.last_tick = Some(stamp);
+                if self.running {
+                    self.last_pulse = Some((stamp, self.next_pulse));
+                    self.next_pulse += 1;
+                }
+            }
+            START => {
+                self.running = true;
+                self.next_pulse = 0;
+                self.last_pulse = None;
+            }
+            CONTINUE => self.running = true,
+            STOP => self.running = false,
+            SONG_POSITION => {
+                let sixteenths = (data[0] as u64) | ((data[1] as u64) << 7);
+                self.next_pulse = sixteenths * PULSES_PER_SIXTEENTH;
+                self.last_pulse = None;
+            }
+            _ => (),
+        }
+    }
+
+    pub fn on_onset(&mut self, stamp: u64) {
+        self.first_onset.get_or_insert(stamp);
+        self.onsets.push(stamp);
+        if self.onsets.len() > ONSETS {
+            self.onsets.remove(0);
+        }
+    }
+
+    fn has_clock(&self, stamp: u64) -> bool {
+        self.last_tick
+            .is_some_and(|last_tick| stamp.saturating_sub(last_tick) < CLOCK_TIMEOUT)
+    }
+
+    //Median time between onsets, folded into a usual beat length
+    fn inferred_beat(&self) -> Option<u64> {
+        let mut intervals: Vec<u64> = self
+            .onsets
+            .windows(2)
+            .map(|onsets| onsets[1] - onsets[0])
+            .filter(|interval| *interval > 0)
+            .collect();
+        if intervals.len() < 2 {
+            return None;
+        }
+        intervals.sort();
+
+        let mut beat = intervals[intervals.len() / 2];
+        while beat > LONGEST_BEAT {
+            beat /= 2;
+        }
+        while beat < SHORTEST_BEAT {
+            beat *= 2;
+        }
+        Some(beat)
+    }
+
+    //Beat length in microseconds, a fixed tempo (in bpm) replaces the inferred one
+    fn beat_length(&self, stamp: u64, bpm: Option<u32>) -> Option<u64> {
+        match (self.has_clock(stamp), bpm) {
+            (true, _) => self.pulse_length.map(|length| length * PULSES_PER_BEAT),
+            (false, Some(bpm)) => Some(60_000_000 / bpm.max(1) as u64),
+            (false, None) => self.inferred_beat(),
+        }
+    }
+
+    //Number of the beat (from 0) nearest to the stamp
+    fn beat_at(&self, stamp: u64, bpm: Option<u32>) -> Option<u64> {
+        let beat_length = self.beat_length(stamp, bpm)?.max(1);
+
+        //Pulses are counted from the start or the song position, without them the clock only
+        //gives the tempo
+        if let (true, Some((last_stamp, pulse))) = (self.has_clock(stamp), self.last_pulse) {
+            let pulse_length = self.pulse_length?.max(1);
+            let pulses = match self.running {
+                true => {
+                    pulse + (stamp.saturating_sub(last_stamp) + pulse_length / 2) / pulse_length
+                }
+                false => pulse,
+            };
+            return Some((pulses + PULSES_PER_BEAT / 2) / PULSES_PER_BEAT);
+        }
+
+        let origin = self.first_onset?;
+        Some((stamp.saturating_sub(origin) + beat_length / 2) / beat_length)
+    }
+
+    pub fn position(
+        &self,
+        stamp: u64,
+        bpm: Option<u32>,
+        time_signature: TimeSignature,
+    ) -> Option<Position> {
+        let beat = self.beat_at(stamp, bpm)?;
+        let beat_length = self.beat_length(stamp, bpm)?.max(1);
+        let beats_per_bar = time_signature.beats.max(1) as u64;
+
+        Some(Position {
+            bar: beat / beats_per_bar + 1,
+            beat: beat % beats_per_bar + 1,
+            bpm: ((60_000_000 + beat_length / 2) / beat_length) as u32,
+        })
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bar {} beat {} ({} bpm)", self.bar, self.beat, self.bpm)
+    }
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        TimeSignature { beats: 4, unit: 4 }
+    }
+}
+
+impl FromStr for TimeSignature {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid time signature \"{s}\" (examples: 4/4, 3/4, 6/8)");
+        let (beats, unit) = s.split_once('/').ok_or_else(invalid)?;
+        match (beats.parse(), unit.parse()) {
+            (Ok(beats @ 1..), Ok(unit @ (1 | 2 | 4 | 8 | 16))) => Ok(TimeSignature { beats, unit }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Display for TimeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.beats, self.unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Pulse length at 120 bpm
+    const PULSE: u64 = 500_000 / PULSES_PER_BEAT;
+
+    //Sends the pulses from the stamp, returns the stamp of the last one
+    fn pulses(tempo: &mut Tempo, from: u64, count: u64) -> u64 {
+        for i in 0..count {
+            tempo.on_system_message(from + i * PULSE, CLOCK, [0, 0]);
+        }
+        from + (count - 1) * PULSE
+    }
+
+    #[test]
+    fn running_clock_gives_the_position() {
+        let mut tempo = Tempo::default();
+        tempo.on_system_message(0, START, [0, 0]);
+        let stamp = pulses(&mut tempo, 0, 2 * PULSES_PER_BEAT + 1);
+        let position = tempo.position(stamp, None, TimeSignature::default());
+        assert!(
+            position
+                == Some(Position {
+                    bar: 1,
+                    beat: 3,
+                    bpm: 120
+                })
+        );
+    }
+
+    #[test]
+    fn pulses_without_start_give_the_tempo() {
+        let mut tempo = Tempo::default();
+        let stamp = pulses(&mut tempo, 0, 2 * PULSES_PER_BEAT);
+        tempo.on_onset(stamp);
+        let position = tempo.position(stamp, Some(90), TimeSignature::default());
+        assert!(position.is_some_and(|position| position.bpm == 120));
+    }
+
+    #[test]
+    fn stopped_clock_times_out() {
+        let mut tempo = Tempo::default();
+        tempo.on_system_message(0, START, [0, 0]);
+        let stamp = pulses(&mut tempo, 0, PULSES_PER_BEAT);
+        tempo.on_system_message(stamp, STOP, [0, 0]);
+        tempo.on_onset(stamp);
+        assert!(tempo
+            .position(stamp, Some(90), TimeSignature::default())
+            .is_some_and(|position| position.bpm == 120));
+        let later = stamp + 2 * CLOCK_TIMEOUT;
+        assert!(tempo
+            .position(later, Some(90), TimeSignature::default())
+            .is_some_and(|position| position.bpm == 90));
+    }
+}
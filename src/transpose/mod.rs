@@ -0,0 +1,118 @@
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+//Largest transposition, in semitones either way
+const MAX_TRANSPOSE: i8 = 24;
+
+const MAX_CAPO: u8 = 12;
+
+//Written notes of the player instead of the sounding ones
+#[derive(Clone, Copy, PartialEq)]
+pub enum Instrument {
+    Concert,
+    //Clarinet, trumpet, soprano saxophone: written a major second higher (tenor saxophone an octave more)
+    BFlat,
+    //Alto and baritone saxophone: written a major sixth higher
+    EFlat,
+    //French horn: written a fifth higher
+    F,
+    //Chord shapes of a guitar with a capo on this fret: written lower
+    Capo(u8),
+}
+
+impl Instrument {
+    //Semitones from the sounding notes to the written ones
+    pub fn shift(&self) -> i8 {
+        match self {
+            Instrument::Concert => 0,
+            Instrument::BFlat => 2,
+            Instrument::EFlat => 9,
+            Instrument::F => 7,
+            Instrument::Capo(fret) => -(*fret as i8),
+        }
+    }
+}
+
+//Key number moved by some semitones, None out of the midi range
+pub fn transpose(key_number: u8, semitones: i8) -> Option<u8> {
+    u8::try_from(key_number as i16 + semitones as i16)
+        .ok()
+        .filter(|key_number| *key_number < 128)
+}
+
+pub fn parse_transpose(value: &str) -> Result<i8, String> {
+    match value.parse::<i8>() {
+        Ok(semitones) if semitones.abs() <= MAX_TRANSPOSE => Ok(semitones),
+        _ => Err(format!(
+            "Invalid transposition \"{value}\", expected -{MAX_TRANSPOSE} to +{MAX_TRANSPOSE} semitones"
+        )),
+    }
+}
+
+impl FromStr for Instrument {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "concert" => Ok(Instrument::Concert),
+            "bb" | "b♭" => Ok(Instrument::BFlat),
+            "eb" | "e♭" => Ok(Instrument::EFlat),
+            "f" => Ok(Instrument::F),
+            _ => match s.strip_prefix("capo:").map(|fret| fret.parse::<u8>()) {
+                Some(Ok(fret @ 0..=MAX_CAPO)) => Ok(Instrument::Capo(fret)),
+                Some(_) => Err(format!(
+                    "Invalid capo \"{s}\", expected capo:0 to capo:{MAX_CAPO}"
+                )),
+                None => Err(format!(
+                    "Unknown instrument \"{s}\" (available: concert, bb, eb, f, capo:<fret>)"
+                )),
+            },
+        }
+    }
+}
+
+impl Display for Instrument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instrument::Concert => write!(f, "concert"),
+            Instrument::BFlat => write!(f, "bb"),
+            Instrument::EFlat => write!(f, "eb"),
+            Instrument::F => write!(f, "f"),
+            Instrument::Capo(fret) => write!(f, "capo:{fret}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_out_of_the_midi_range_are_dropped() {
+        assert_eq!(transpose(60, -2), Some(58));
+        assert_eq!(transpose(1, -2), None);
+        assert_eq!(transpose(126, 2), None);
+    }
+
+    #[test]
+    fn transposition_is_limited() {
+        assert_eq!(parse_transpose("-24"), Ok(-24));
+        assert_eq!(parse_transpose("+7"), Ok(7));
+        assert!(parse_transpose("25").is_err());
+        assert!(parse_transpose("up").is_err());
+    }
+
+    #[test]
+    fn instruments_shift_to_the_written_notes() {
+        let shifts: Vec<i8> = ["concert", "Bb", "e♭", "f", "capo:2"]
+            .iter()
+            .map(|instrument| instrument.parse::<Instrument>().unwrap().shift())
+            .collect();
+        assert_eq!(shifts, [0, 2, 9, 7, -2]);
+        assert!("capo:13".parse::<Instrument>().is_err());
+        assert!("tuba".parse::<Instrument>().is_err());
+        assert_eq!(Instrument::Capo(2).to_string(), "capo:2");
+    }
+}
@@ -0,0 +1,263 @@
+use crate::{
+    chord::{Chord, Seventh},
+    note::Note,
+};
+
+//Largest move of a voice still counted as a step, in semitones
+const STEP: u8 = 2;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Motion {
+    Common,
+    Step,
+    Leap,
+    //The voice starts or stops sounding
+    Enters,
+    Leaves,
+}
+
+//A voice from one chord to the next, None when it does not sound in one of them
+#[derive(Clone, Copy)]
+pub struct Voice {
+    pub from: Option<Note>,
+    pub to: Option<Note>,
+}
+
+pub enum Warning {
+    ParallelFifths(Voice, Voice),
+    ParallelOctaves(Voice, Voice),
+    //The third of a dominant chord not going up a half step
+    UnresolvedLeadingTone(Voice),
+    //The seventh of a chord not going down a step
+    UnresolvedSeventh(Voice),
+}
+
+pub struct Analysis {
+    pub voices: Vec<Voice>,
+    pub warnings: Vec<Warning>,
+}
+
+impl Voice {
+    //Semitones moved, positive upwards
+    pub fn movement(&self) -> Option<i8> {
+        match (&self.from, &self.to) {
+            (Some(from), Some(to)) => Some(u8::from(to) as i8 - u8::from(from) as i8),
+            _ => None,
+        }
+    }
+
+    pub fn motion(&self) -> Motion {
+        match self.movement() {
+            Some(0) => Motion::Common,
+            Some(movement) if movement.unsigned_abs() <= STEP => Motion::Step,
+            Some(_) => Motion::Leap,
+            None if self.from.is_none() => Motion::Enters,
+            None => Motion::Leaves,
+        }
+    }
+}
+
+impl Analysis {
+    pub fn count(&self, motion: Motion) -> usize {
+        self.voices
+            .iter()
+            .filter(|voice| voice.motion() == motion)
+            .count()
+    }
+
+    //Semitones moved by all the voices
+    pub fn total_movement(&self) -> u32 {
+        self.voices
+            .iter()
+            .filter_map(|voice| voice.movement())
+            .map(|movement| movement.unsigned_abs() as u32)
+            .sum()
+    }
+}
+
+//Bass goes to bass and top to top, the inner voices to the closest note left
+fn pair(from: &[Note], to: &[Note]) -> Vec<Voice> {
+    if from.len() == to.len() {
+        return from
+            .iter()
+            .zip(to)
+            .map(|(from, to)| Voice {
+                from: Some(*from),
+                to: Some(*to),
+            })
+            .collect();
+    }
+
+    let mut from_left: Vec<Option<Note>> = from.iter().map(|note| Some(*note)).collect();
+    let mut to_left: Vec<Option<Note>> = to.iter().map(|note| Some(*note)).collect();
+    let mut voices = vec![];
+    //A single note is the bass, it is not paired with the top voice again
+    let outer = [(0, 0), (from.len() - 1, to.len() - 1)];
+    for (i, j) in outer {
+        if let (Some(from), Some(to)) = (from_left[i], to_left[j]) {
+            from_left[i] = None;
+            to_left[j] = None;
+            voices.push(Voice {
+                from: Some(from),
+                to: Some(to),
+            });
+        }
+    }
+
+    loop {
+        let closest = from_left
+            .iter()
+            .enumerate()
+            .filter_map(|(i, from)| from.map(|from| (i, from)))
+            .flat_map(|(i, from)| {
+                to_left
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(j, to)| to.map(|to| (i, j, from, to)))
+            })
+            .min_by_key(|(_, _, from, to)| (u8::from(from) as i8 - u8::from(to) as i8).abs());
+        let Some((i, j, from, to)) = closest else {
+            break;
+        };
+        from_left[i] = None;
+        to_left[j] = None;
+        voices.push(Voice {
+            from: Some(from),
+            to: Some(to),
+        });
+    }
+
+    voices.extend(from_left.into_iter().flatten().map(|from| Voice {
+        from: Some(from),
+        to: None,
+    }));
+    voices.extend(to_left.into_iter().flatten().map(|to| Voice {
+        from: None,
+        to: Some(to),
+    }));
+    voices.sort_by_key(|voice| voice.from.or(voice.to).map(|note| u8::from(&note)));
+    voices
+}
+
+//Two voices a fifth or an octave apart moving the same way to the same interval
+fn parallels(voices: &[Voice]) -> Vec<Warning> {
+    let mut warnings = vec![];
+    for (i, lower) in voices.iter().enumerate() {
+        for upper in &voices[i + 1..] {
+            let (Some(lower_move), Some(upper_move)) = (lower.movement(), upper.movement()) else {
+                continue;
+            };
+            if lower_move == 0 || lower_move.signum() != upper_move.signum() {
+                continue;
+            }
+            let interval = |lower: &Note, upper: &Note| {
+                (u8::from(upper) as i8 - u8::from(lower) as i8).rem_euclid(12)
+            };
+            let (Some(lower_from), Some(lower_to), Some(upper_from), Some(upper_to)) =
+                (lower.from, lower.to, upper.from, upper.to)
+            else {
+                continue;
+            };
+            let before = interval(&lower_from, &upper_from);
+            let after = interval(&lower_to, &upper_to);
+            match (before, after) {
+                (7, 7) => warnings.push(Warning::ParallelFifths(*lower, *upper)),
+                (0, 0) => warnings.push(Warning::ParallelOctaves(*lower, *upper)),
+                _ => (),
+            }
+        }
+    }
+    warnings
+}
+
+//Tendency tones of the first chord, only checked when the chord changes
+fn resolutions(voices: &[Voice], chord: &Chord) -> Vec<Warning> {
+    let seventh = chord.extension.map(|extension| match extension.seventh {
+        Seventh::Diminished => 9,
+        Seventh::Minor => 10,
+        Seventh::Major => 11,
+    });
+
+    let mut warnings = vec![];
+    for voice in voices {
+        let (Some(from), Some(movement)) = (voice.from, voice.movement()) else {
+            continue;
+        };
+        let interval = chord.interval(&from);
+        if chord.is_dominant() && interval == 4 && movement != 1 {
+            warnings.push(Warning::UnresolvedLeadingTone(*voice));
+        }
+        if seventh == Some(interval) && !(-(STEP as i8)..=-1).contains(&movement) {
+            warnings.push(Warning::UnresolvedSeventh(*voice));
+        }
+    }
+    warnings
+}
+
+//Motion of each voice between two held note sets, notes are expected sorted by key number
+pub fn analyse(
+    from: &[Note],
+    from_chord: Option<&Chord>,
+    to: &[Note],
+    to_chord: Option<&Chord>,
+) -> Analysis {
+    if from.is_empty() || to.is_empty() {
+        return Analysis {
+            voices: vec![],
+            warnings: vec![],
+        };
+    }
+
+    let voices = pair(from, to);
+    let mut warnings = parallels(&voices);
+
+    let changed = match (from_chord, to_chord) {
+        (Some(from), Some(to)) => {
+            from.root.pitch_class() != to.root.pitch_class() || from.quality != to.quality
+        }
+        _ => false,
+    };
+    if let (true, Some(chord)) = (changed, from_chord) {
+        warnings.extend(resolutions(&voices, chord));
+    }
+
+    Analysis { voices, warnings }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::notes;
+
+    #[test]
+    fn single_note_keeps_every_voice() {
+        let analysis = analyse(&notes(&[48]), None, &notes(&[48, 52, 55]), None);
+        assert_eq!(analysis.voices.len(), 3);
+        assert_eq!(analysis.count(Motion::Common), 1);
+        assert_eq!(analysis.count(Motion::Enters), 2);
+
+        let analysis = analyse(&notes(&[48, 52, 55]), None, &notes(&[43]), None);
+        assert_eq!(analysis.voices.len(), 3);
+        assert_eq!(analysis.count(Motion::Leap), 1);
+        assert_eq!(analysis.count(Motion::Leaves), 2);
+    }
+
+    #[test]
+    fn outer_voices_are_paired() {
+        //C E G C to B D G: bass down a step, top voice down a fourth to G
+        let analysis = analyse(&notes(&[48, 52, 55, 60]), None, &notes(&[47, 50, 55]), None);
+        assert_eq!(analysis.voices.len(), 4);
+        let bass = analysis.voices[0];
+        assert_eq!(bass.movement(), Some(-1));
+        assert_eq!(analysis.count(Motion::Leaves), 1);
+    }
+
+    #[test]
+    fn parallel_fifths_are_flagged() {
+        let analysis = analyse(&notes(&[48, 55]), None, &notes(&[50, 57]), None);
+        assert!(matches!(
+            analysis.warnings.as_slice(),
+            [Warning::ParallelFifths(_, _)]
+        ));
+    }
+}
@@ -0,0 +1,270 @@
+use crate::{
+    chord::{self, Chord, Quality, Stacking, Upper},
+    note::Note,
+    score::{Component, Scoring},
+};
+
+//Tensions below this key sound muddy
+const LOW_REGISTER: u8 = 48;
+
+//Tensions sit best on top of the voicing, they cost 1 under the lowest guide tone and 2 in the low register
+pub fn register_units(chord: &Chord, notes: &[Note]) -> u32 {
+    let lowest_guide_tone = notes
+        .iter()
+        .filter(|note| chord.is_guide_tone(note))
+        .map(u8::from)
+        .min();
+
+    notes
+        .iter()
+        .filter(|note| chord.is_tension(note))
+        .map(|note| {
+            let key_number = u8::from(note);
+            if key_number < LOW_REGISTER {
+                2
+            } else if lowest_guide_tone.is_some_and(|guide_tone| key_number < guide_tone) {
+                1
+            } else {
+                0
+            }
+        })
+        .sum()
+}
+
+fn pitch_classes(notes: &[Note]) -> u16 {
+    notes
+        .iter()
+        .fold(0, |bitmap, note| bitmap | (0b1 << note.pitch_class()))
+}
+
+//Best chord built on the upper notes, in any inversion and without added tensions
+fn upper_chord(notes: &[Note], scoring: &Scoring) -> Option<Chord> {
+    notes
+        .iter()
+        .filter_map(|root| chord::to_chord_root(notes, root, &scoring.weights))
+        .filter(|chord| {
+            !matches!(chord.quality, Quality::Power | Quality::Suspended)
+                && chord.tensions.is_empty()
+        })
+        .min_by_key(|chord| chord.weight())
+}
+
+//Splits the voicing by register into two stacked chords ("D/C7", "E♭|C", "C7 UST II"), with a
+//keyboard split the right hand is the upper chord
+pub fn polychords(notes: &[Note], split: Option<&Note>, scoring: &Scoring) -> Vec<Chord> {
+    let mut chords: Vec<Chord> = vec![];
+
+    //The hands give the division, it is not weighted as a guess
+    let polychord_weight = match split {
+        Some(_) => 0,
+        None => scoring.weights.polychord,
+    };
+    let splits = match split {
+        Some(split) => {
+            let left_hand = notes
+                .iter()
+                .filter(|note| u8::from(*note) < u8::from(split))
+                .count();
+            left_hand..left_hand + 1
+        }
+        None => 2..notes.len(),
+    };
+    for split_at in splits {
+        let (lower, upper) = notes.split_at(split_at);
+
+        //Octave doublings stay on the same side of the split, unless the hands divide them
+        if split.is_none()
+            && lower.last().map(Note::pitch_class) == upper.first().map(Note::pitch_class)
+        {
+            continue;
+        }
+        if pitch_classes(lower).count_ones() < 2 || pitch_classes(upper).count_ones() < 3 {
+            continue;
+        }
+
+        let Some(upper_chord) = upper_chord(upper, scoring) else {
+            continue;
+        };
+        let Some(lower_chord) = chord::candidates(lower, &lower[0], scoring)
+            .into_iter()
+            .filter(|chord| chord.tensions.is_empty())
+            .min_by_key(|chord| chord.weight())
+        else {
+            continue;
+        };
+
+        //The upper chord has to bring tensions to the lower one
+        if upper_chord.root.pitch_class() == lower_chord.root.pitch_class()
+            || upper.iter().all(|note| !lower_chord.is_tension(note))
+        {
+            continue;
+        }
+
+        //Upper structure triads are read over a dominant seventh, the right hand triad only as one
+        let upper_structure = lower_chord.is_dominant()
+            && matches!(upper_chord.quality, Quality::Major | Quality::Minor)
+            && upper_chord.extension.is_none();
+
+        if !(upper_structure && split.is_some()) {
+            let mut polychord = lower_chord.clone();
+            polychord.score.add(
+                Component::Polychord,
+                upper_chord.weight() + polychord_weight,
+            );
+            polychord.upper = Some(Upper {
+                chord: Box::new(upper_chord.clone()),
+                stacking: Stacking::Polychord,
+            });
+            push_best(&mut chords, polychord);
+        }
+
+        if upper_structure {
+            let mut upper_structure = lower_chord;
+            upper_structure.score.add(
+                Component::UpperStructure,
+                upper_chord.weight() + scoring.weights.upper_structure,
+            );
+            upper_structure.upper = Some(Upper {
+                chord: Box::new(upper_chord),
+                stacking: Stacking::UpperStructure,
+            });
+            push_best(&mut chords, upper_structure);
+        }
+    }
+
+    chords
+}
+
+//Different splits can give the same stacked chords, only the lightest one is kept
+fn push_best(chords: &mut Vec<Chord>, chord: Chord) {
+    let same_stack = |other: &Chord| {
+        other.root.pitch_class() == chord.root.pitch_class()
+            && other.quality == chord.quality
+            && other.extension == chord.extension
+            && match (&other.upper, &chord.upper) {
+                (Some(a), Some(b)) => {
+                    a.stacking == b.stacking
+                        && a.chord.root.pitch_class() == b.chord.root.pitch_class()
+                        && a.chord.quality == b.chord.quality
+                }
+                _ => false,
+            }
+    };
+
+    match chords.iter_mut().find(|other| same_stack(other)) {
+        Some(other) if other.weight() > chord.weight() => *other = chord,
+        Some(_) => (),
+        None => chords.push(chord),
+    }
+}
+
+//Accented notes are heard as chord tones, each accented tension costs the accent weight
+pub fn weight_accents(chords: &mut [Chord], accented: &[Note], scoring: &Scoring) {
+    if accented.is_empty() {
+        return;
+    }
+
+    for chord in chords.iter_mut() {
+        let accented_tensions = accented
+            .iter()
+            .filter(|note| chord.is_tension(note))
+            .count() as u32;
+        chord.score.add(
+            Component::Accent,
+            scoring.weights.accent * accented_tensions,
+        );
+    }
+
+    chords.sort_by_key(|chord| chord.weight());
+}
+
+//With a keyboard split the root is expected in the left hand, other roots cost the split weight.
+//A single left hand note is the bass of a slash chord, any root is then expected
+pub fn weight_split(chords: &mut [Chord], left_hand: &[Note], scoring: &Scoring) {
+    let left_hand = pitch_classes(left_hand);
+    if left_hand.count_ones() < 2 {
+        return;
+    }
+
+    for chord in chords.iter_mut() {
+        if left_hand & (0b1 << chord.root.pitch_class()) == 0 {
+            chord.score.add(Component::Split, scoring.weights.split);
+        }
+    }
+
+    chords.sort_by_key(|chord| chord.weight());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, note::notes};
+
+    //Best chord of the keys as the keyboard names them with a split
+    fn name(key_numbers: &[u8], split: Option<u8>) -> String {
+        let notes = notes(key_numbers);
+        let config = Config::default();
+        let split = split.and_then(|key| Note::from(key).map(|(note, _)| note));
+        let mut chords = chord::to_chord_split(&notes, &notes[0], split.as_ref(), &config.scoring);
+        if let Some(split) = &split {
+            let left_hand: Vec<Note> = notes
+                .iter()
+                .filter(|note| u8::from(*note) < u8::from(split))
+                .copied()
+                .collect();
+            weight_split(&mut chords, &left_hand, &config.scoring);
+        }
+        config.notation().chord(&chords[0])
+    }
+
+    #[test]
+    fn low_tensions_cost_register_units() {
+        let config = Config::default();
+        //C9 voicings, the ninth on top, under the third and in the low register
+        let c9 = &chord::to_chord(&notes(&[48, 52, 58, 62]), &notes(&[48])[0], &config.scoring)[0];
+        assert_eq!(register_units(c9, &notes(&[48, 64, 70, 74])), 0);
+        assert_eq!(register_units(c9, &notes(&[48, 62, 64, 70])), 1);
+        assert_eq!(register_units(c9, &notes(&[38, 48, 64, 70])), 2);
+    }
+
+    #[test]
+    fn stacked_chords_are_found_by_register() {
+        //C and D triads, also heard as D11/C
+        let config = Config::default();
+        let notes = notes(&[48, 52, 55, 62, 66, 69]);
+        let names: Vec<String> = polychords(&notes, None, &config.scoring)
+            .iter()
+            .map(|chord| config.notation().chord(chord))
+            .collect();
+        assert_eq!(names, ["D|C"]);
+        assert_eq!(name(&[48, 52, 55, 58, 62, 66, 69], None), "C7 UST II");
+        assert_eq!(name(&[48, 52, 55, 58, 64, 69, 73], None), "C7 UST VI");
+    }
+
+    #[test]
+    fn accented_tensions_change_the_chord() {
+        let config = Config::default();
+        let notes = notes(&[60, 64, 67, 69]);
+        let mut chords = chord::to_chord(&notes, &notes[0], &config.scoring);
+        assert_eq!(config.notation().chord(&chords[0]), "C(13)");
+        weight_accents(&mut chords, &notes[3..], &config.scoring);
+        assert_eq!(config.notation().chord(&chords[0]), "Amin7/C");
+    }
+
+    #[test]
+    fn stacked_chords_are_divided_at_the_split() {
+        //Cmaj7 in the left hand, D in the right one
+        assert_eq!(name(&[48, 52, 55, 59, 62, 66, 69], None), "D13/C");
+        assert_eq!(name(&[48, 52, 55, 59, 62, 66, 69], Some(60)), "D/Cmaj7");
+    }
+
+    #[test]
+    fn right_hand_triad_is_an_upper_structure() {
+        assert_eq!(name(&[48, 52, 55, 58, 62, 66, 69], Some(60)), "C7 UST II");
+    }
+
+    #[test]
+    fn single_left_hand_note_is_a_slash_bass() {
+        assert_eq!(name(&[48, 62, 66, 69], Some(60)), "D7/C");
+    }
+}